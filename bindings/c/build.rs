@@ -0,0 +1,24 @@
+use std::env;
+use std::path::PathBuf;
+
+// The committed include/neo_replay.h is what C users build against. It is only
+// rewritten when NEO_REPLAY_UPDATE_HEADER is set, after changing the API; other
+// builds generate into OUT_DIR so a broken header still fails the build.
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=NEO_REPLAY_UPDATE_HEADER");
+
+    let output = match env::var_os("NEO_REPLAY_UPDATE_HEADER") {
+        Some(_) => crate_dir.join("include/neo_replay.h"),
+        None => PathBuf::from(env::var("OUT_DIR").unwrap()).join("neo_replay.h"),
+    };
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).expect("Invalid cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Failed to generate C header")
+        .write_to_file(output);
+}
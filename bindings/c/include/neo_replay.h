@@ -0,0 +1,157 @@
+#ifndef NEO_REPLAY_H
+#define NEO_REPLAY_H
+
+/* Generated by cbindgen from bindings/c/src/lib.rs, do not edit */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Raised whenever a signature, struct layout or status meaning changes incompatibly
+ */
+#define NEO_REPLAY_ABI_VERSION 1
+
+typedef enum NeoReplayStatus {
+  NEO_REPLAY_STATUS_OK = 0,
+  NEO_REPLAY_STATUS_ERROR = 1,
+  NEO_REPLAY_STATUS_NULL_POINTER = 2,
+  NEO_REPLAY_STATUS_BUFFER_TOO_SMALL = 3,
+} NeoReplayStatus;
+
+/**
+ * Opaque handle holding a parsed replay and a renderer positioned at some action
+ */
+typedef struct NeoReplay NeoReplay;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * ABI version of the library, to compare with `NEO_REPLAY_ABI_VERSION` from the header
+ */
+uint32_t neo_replay_abi_version(void);
+
+/**
+ * Parse a `.pch` file from memory. Returns NULL on failure.
+ *
+ * Files are checked against the crate's default `Limits` for untrusted uploads, and
+ * each step, seek or render call fails after 60 seconds of rendering.
+ *
+ * # Safety
+ *
+ * `data` must point to `len` readable bytes. The bytes are copied and may be
+ * freed once this returns.
+ */
+struct NeoReplay *neo_replay_open(const uint8_t *data, size_t len);
+
+/**
+ * Release a handle returned by `neo_replay_open`. NULL is ignored.
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a handle that has not been freed yet.
+ */
+void neo_replay_free(struct NeoReplay *replay);
+
+/**
+ * Canvas width in pixels, or 0 for NULL
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a live handle.
+ */
+uint32_t neo_replay_width(const struct NeoReplay *replay);
+
+/**
+ * Canvas height in pixels, or 0 for NULL
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a live handle.
+ */
+uint32_t neo_replay_height(const struct NeoReplay *replay);
+
+/**
+ * Number of actions in the replay, or 0 for NULL
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a live handle.
+ */
+size_t neo_replay_action_count(const struct NeoReplay *replay);
+
+/**
+ * Number of actions applied to the canvas so far, or 0 for NULL
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a live handle.
+ */
+size_t neo_replay_position(const struct NeoReplay *replay);
+
+/**
+ * Apply up to `count` further actions, stopping at the end of the replay
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a live handle.
+ */
+enum NeoReplayStatus neo_replay_step(struct NeoReplay *replay, size_t count);
+
+/**
+ * Move to the state after `position` actions, replaying from the start when going back
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a live handle.
+ */
+enum NeoReplayStatus neo_replay_seek(struct NeoReplay *replay, size_t position);
+
+/**
+ * Write the current composite as opaque RGBA, row by row, into `out`.
+ * `out_len` must be at least width * height * 4.
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a live handle and `out` must point to `out_len` writable bytes.
+ */
+enum NeoReplayStatus neo_replay_composite_rgba(const struct NeoReplay *replay,
+                                               uint8_t *out,
+                                               size_t out_len);
+
+/**
+ * Replay to the last action and write the final image like `neo_replay_composite_rgba`
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a live handle and `out` must point to `out_len` writable bytes.
+ */
+enum NeoReplayStatus neo_replay_render_final(struct NeoReplay *replay,
+                                             uint8_t *out,
+                                             size_t out_len);
+
+/**
+ * Straight-alpha RGBA pixels of a single layer, with the same size requirement as
+ * `neo_replay_composite_rgba`
+ *
+ * # Safety
+ *
+ * `replay` must be NULL or a live handle and `out` must point to `out_len` writable bytes.
+ */
+enum NeoReplayStatus neo_replay_layer_rgba(const struct NeoReplay *replay,
+                                           size_t layer,
+                                           uint8_t *out,
+                                           size_t out_len);
+
+/**
+ * Message for the most recent failure on this thread, or NULL. The pointer stays
+ * valid until the next failing call on the same thread.
+ */
+const char *neo_replay_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NEO_REPLAY_H */
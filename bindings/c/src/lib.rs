@@ -0,0 +1,337 @@
+//! C API for embedding the replay renderer, declared in `include/neo_replay.h`.
+//! Regenerate the header with `NEO_REPLAY_UPDATE_HEADER=1 cargo build -p neo-replay-c`
+//! after changing the API.
+//!
+//! ```c
+//! NeoReplay *replay = neo_replay_open(data, len);
+//! if (!replay) {
+//!     fprintf(stderr, "%s\n", neo_replay_last_error());
+//!     return;
+//! }
+//! size_t size = (size_t)neo_replay_width(replay) * neo_replay_height(replay) * 4;
+//! uint8_t *pixels = malloc(size);
+//! neo_replay_render_final(replay, pixels, size);
+//! neo_replay_free(replay);
+//! ```
+//!
+//! Functions never unwind into the caller; failures return an error status (or
+//! NULL, or 0 for getters) and leave a message for `neo_replay_last_error` on the
+//! calling thread. Check `neo_replay_abi_version()` against `NEO_REPLAY_ABI_VERSION`
+//! to detect a library built from a different header.
+
+use anyhow::{anyhow, bail, Result};
+use neo_replay_rs::limits::Limits;
+use neo_replay_rs::renderer::Renderer;
+use neo_replay_rs::PchFile;
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// Raised whenever a signature, struct layout or status meaning changes incompatibly
+pub const NEO_REPLAY_ABI_VERSION: u32 = 1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeoReplayStatus {
+    Ok = 0,
+    Error = 1,
+    NullPointer = 2,
+    BufferTooSmall = 3,
+}
+
+/// Opaque handle holding a parsed replay and a renderer positioned at some action
+pub struct NeoReplay {
+    pch: PchFile,
+    renderer: Renderer,
+    position: usize, // Number of actions applied to the canvas
+}
+
+impl NeoReplay {
+    fn step(&mut self, count: usize) -> Result<()> {
+        // Each call gets the whole render time budget, so a slow player is never cut off
+        self.renderer.set_time_limit(Limits::default().max_render_time);
+        let end = self.position.saturating_add(count).min(self.pch.actions.len());
+        while self.position < end {
+            self.renderer.step(&self.pch.actions[self.position])?;
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    fn seek(&mut self, position: usize) -> Result<()> {
+        let position = position.min(self.pch.actions.len());
+        if position < self.position {
+            self.renderer = blank_renderer(&self.pch);
+            self.position = 0;
+        }
+        self.step(position - self.position)
+    }
+
+    fn write_composite(&self, out: &mut [u8]) {
+        let composite = self.renderer.canvas.composite();
+        for (dst, src) in out.chunks_exact_mut(4).zip(composite.pixels()) {
+            dst.copy_from_slice(&[src[0], src[1], src[2], 255]);
+        }
+    }
+
+    fn rgba_size(&self) -> usize {
+        self.renderer.canvas.width as usize * self.renderer.canvas.height as usize * 4
+    }
+}
+
+fn blank_renderer(pch: &PchFile) -> Renderer {
+    let mut renderer = Renderer::new(pch.header.width as u32, pch.header.height as u32);
+    renderer.canvas.clear();
+    renderer
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+fn null_pointer(name: &str) -> NeoReplayStatus {
+    set_last_error(format!("{} is NULL", name));
+    NeoReplayStatus::NullPointer
+}
+
+// Handle passed in from C, recording an error when it is NULL
+unsafe fn handle<'a>(replay: *const NeoReplay) -> Option<&'a NeoReplay> {
+    let handle = replay.as_ref();
+    if handle.is_none() {
+        null_pointer("replay");
+    }
+    handle
+}
+
+unsafe fn handle_mut<'a>(replay: *mut NeoReplay) -> Option<&'a mut NeoReplay> {
+    let handle = replay.as_mut();
+    if handle.is_none() {
+        null_pointer("replay");
+    }
+    handle
+}
+
+/// Run `f`, turning errors and panics into `NeoReplayStatus::Error`
+fn guard(f: impl FnOnce() -> Result<NeoReplayStatus>) -> NeoReplayStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(status)) => status,
+        Ok(Err(error)) => {
+            set_last_error(error.to_string());
+            NeoReplayStatus::Error
+        }
+        Err(_) => {
+            set_last_error("Renderer panicked".to_string());
+            NeoReplayStatus::Error
+        }
+    }
+}
+
+/// ABI version of the library, to compare with `NEO_REPLAY_ABI_VERSION` from the header
+#[no_mangle]
+pub extern "C" fn neo_replay_abi_version() -> u32 {
+    NEO_REPLAY_ABI_VERSION
+}
+
+/// Parse a `.pch` file from memory. Returns NULL on failure.
+///
+/// Files are checked against the crate's default `Limits` for untrusted uploads, and
+/// each step, seek or render call fails after 60 seconds of rendering.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes. The bytes are copied and may be
+/// freed once this returns.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_open(data: *const u8, len: usize) -> *mut NeoReplay {
+    let mut replay = None;
+    let status = guard(|| {
+        if data.is_null() {
+            bail!("data is NULL");
+        }
+        let mut pch = PchFile::from_bytes_with_limits(slice::from_raw_parts(data, len), &Limits::default())?;
+        pch.fix_actions();
+        let renderer = blank_renderer(&pch);
+        replay = Some(Box::new(NeoReplay { pch, renderer, position: 0 }));
+        Ok(NeoReplayStatus::Ok)
+    });
+
+    match (status, replay) {
+        (NeoReplayStatus::Ok, Some(replay)) => Box::into_raw(replay),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Release a handle returned by `neo_replay_open`. NULL is ignored.
+///
+/// # Safety
+///
+/// `replay` must be NULL or a handle that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_free(replay: *mut NeoReplay) {
+    if !replay.is_null() {
+        drop(Box::from_raw(replay));
+    }
+}
+
+/// Canvas width in pixels, or 0 for NULL
+///
+/// # Safety
+///
+/// `replay` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_width(replay: *const NeoReplay) -> u32 {
+    handle(replay).map_or(0, |replay| replay.renderer.canvas.width)
+}
+
+/// Canvas height in pixels, or 0 for NULL
+///
+/// # Safety
+///
+/// `replay` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_height(replay: *const NeoReplay) -> u32 {
+    handle(replay).map_or(0, |replay| replay.renderer.canvas.height)
+}
+
+/// Number of actions in the replay, or 0 for NULL
+///
+/// # Safety
+///
+/// `replay` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_action_count(replay: *const NeoReplay) -> usize {
+    handle(replay).map_or(0, |replay| replay.pch.actions.len())
+}
+
+/// Number of actions applied to the canvas so far, or 0 for NULL
+///
+/// # Safety
+///
+/// `replay` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_position(replay: *const NeoReplay) -> usize {
+    handle(replay).map_or(0, |replay| replay.position)
+}
+
+/// Apply up to `count` further actions, stopping at the end of the replay
+///
+/// # Safety
+///
+/// `replay` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_step(replay: *mut NeoReplay, count: usize) -> NeoReplayStatus {
+    let Some(replay) = handle_mut(replay) else {
+        return NeoReplayStatus::NullPointer;
+    };
+    guard(|| replay.step(count).map(|_| NeoReplayStatus::Ok))
+}
+
+/// Move to the state after `position` actions, replaying from the start when going back
+///
+/// # Safety
+///
+/// `replay` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_seek(replay: *mut NeoReplay, position: usize) -> NeoReplayStatus {
+    let Some(replay) = handle_mut(replay) else {
+        return NeoReplayStatus::NullPointer;
+    };
+    guard(|| replay.seek(position).map(|_| NeoReplayStatus::Ok))
+}
+
+/// Write the current composite as opaque RGBA, row by row, into `out`.
+/// `out_len` must be at least width * height * 4.
+///
+/// # Safety
+///
+/// `replay` must be NULL or a live handle and `out` must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_composite_rgba(
+    replay: *const NeoReplay,
+    out: *mut u8,
+    out_len: usize,
+) -> NeoReplayStatus {
+    let Some(replay) = handle(replay) else {
+        return NeoReplayStatus::NullPointer;
+    };
+    if out.is_null() {
+        return null_pointer("out");
+    }
+    if out_len < replay.rgba_size() {
+        set_last_error(format!("Buffer needs {} bytes, got {}", replay.rgba_size(), out_len));
+        return NeoReplayStatus::BufferTooSmall;
+    }
+
+    let out = slice::from_raw_parts_mut(out, out_len);
+    guard(|| {
+        replay.write_composite(out);
+        Ok(NeoReplayStatus::Ok)
+    })
+}
+
+/// Replay to the last action and write the final image like `neo_replay_composite_rgba`
+///
+/// # Safety
+///
+/// `replay` must be NULL or a live handle and `out` must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_render_final(replay: *mut NeoReplay, out: *mut u8, out_len: usize) -> NeoReplayStatus {
+    let Some(handle) = handle_mut(replay) else {
+        return NeoReplayStatus::NullPointer;
+    };
+    let status = guard(|| handle.seek(usize::MAX).map(|_| NeoReplayStatus::Ok));
+    if status != NeoReplayStatus::Ok {
+        return status;
+    }
+    neo_replay_composite_rgba(replay, out, out_len)
+}
+
+/// Straight-alpha RGBA pixels of a single layer, with the same size requirement as
+/// `neo_replay_composite_rgba`
+///
+/// # Safety
+///
+/// `replay` must be NULL or a live handle and `out` must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn neo_replay_layer_rgba(
+    replay: *const NeoReplay,
+    layer: usize,
+    out: *mut u8,
+    out_len: usize,
+) -> NeoReplayStatus {
+    let Some(replay) = handle(replay) else {
+        return NeoReplayStatus::NullPointer;
+    };
+    if out.is_null() {
+        return null_pointer("out");
+    }
+    if out_len < replay.rgba_size() {
+        set_last_error(format!("Buffer needs {} bytes, got {}", replay.rgba_size(), out_len));
+        return NeoReplayStatus::BufferTooSmall;
+    }
+
+    let out = slice::from_raw_parts_mut(out, out_len);
+    guard(|| {
+        let image = replay
+            .renderer
+            .canvas
+            .get_layer(layer)
+            .ok_or_else(|| anyhow!("Layer {} does not exist", layer))?;
+        out[..image.as_raw().len()].copy_from_slice(image.as_raw());
+        Ok(NeoReplayStatus::Ok)
+    })
+}
+
+/// Message for the most recent failure on this thread, or NULL. The pointer stays
+/// valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn neo_replay_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| error.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
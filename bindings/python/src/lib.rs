@@ -0,0 +1,361 @@
+//! Python bindings, built with `maturin build` from this directory.
+//!
+//! ```python
+//! import neo_replay
+//!
+//! pch = neo_replay.PchFile.from_file("drawing.pch")  # or limits=neo_replay.Limits(max_render_time=5)
+//! strokes = [a for a in pch.actions if a.points]
+//! for frame in neo_replay.Renderer(pch):
+//!     ...  # numpy array of shape (height, width, 3)
+//! ```
+
+use neo_replay_rs::action::Action as ActionKind;
+use neo_replay_rs::limits::Limits as ReplayLimits;
+use neo_replay_rs::renderer::Renderer as CanvasRenderer;
+use neo_replay_rs::{self as replay, ActionValue};
+use numpy::{PyArray1, PyArray3, PyArrayMethods};
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyList};
+use std::time::Duration;
+
+fn to_py_err(error: anyhow::Error) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// Bounds on the work a replay may cause. Unset arguments keep the defaults for
+/// untrusted uploads; `max_render_time` is in seconds per `Renderer` call.
+#[pyclass(frozen)]
+#[derive(Clone, Default)]
+pub struct Limits {
+    inner: ReplayLimits,
+}
+
+#[pymethods]
+impl Limits {
+    #[new]
+    #[pyo3(signature = (*, max_decompressed_size = None, max_canvas_area = None, max_actions = None, max_points_per_action = None, max_render_time = None))]
+    fn new(
+        max_decompressed_size: Option<usize>,
+        max_canvas_area: Option<u64>,
+        max_actions: Option<usize>,
+        max_points_per_action: Option<usize>,
+        max_render_time: Option<f64>,
+    ) -> PyResult<Self> {
+        let mut inner = ReplayLimits::default();
+        inner.max_decompressed_size = max_decompressed_size.unwrap_or(inner.max_decompressed_size);
+        inner.max_canvas_area = max_canvas_area.unwrap_or(inner.max_canvas_area);
+        inner.max_actions = max_actions.unwrap_or(inner.max_actions);
+        inner.max_points_per_action = max_points_per_action.unwrap_or(inner.max_points_per_action);
+        if let Some(seconds) = max_render_time {
+            let limit = Duration::try_from_secs_f64(seconds)
+                .map_err(|_| PyValueError::new_err(format!("Invalid render time: {}", seconds)))?;
+            inner.max_render_time = Some(limit);
+        }
+        Ok(Limits { inner })
+    }
+
+    /// No bounds at all, for trusted files only
+    #[staticmethod]
+    fn unlimited() -> Self {
+        Limits { inner: ReplayLimits::unlimited() }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Limits(max_decompressed_size={}, max_canvas_area={}, max_actions={}, max_points_per_action={}, max_render_time={})",
+            self.inner.max_decompressed_size,
+            self.inner.max_canvas_area,
+            self.inner.max_actions,
+            self.inner.max_points_per_action,
+            self.inner.max_render_time.map_or("None".to_string(), |limit| limit.as_secs_f64().to_string())
+        )
+    }
+}
+
+/// A parsed `.pch` replay
+#[pyclass(frozen)]
+pub struct PchFile {
+    inner: replay::PchFile,
+    limits: ReplayLimits, // Parsed with these; renderers of this file use the render time
+}
+
+#[pymethods]
+impl PchFile {
+    /// Parse the contents of a `.pch` file. `eraseAll` actions are normalized unless `fix` is false.
+    /// Files going over `limits`, by default `Limits()`, raise `ValueError`.
+    #[staticmethod]
+    #[pyo3(signature = (data, fix = true, limits = None))]
+    fn from_bytes(data: &Bound<'_, PyBytes>, fix: bool, limits: Option<Limits>) -> PyResult<Self> {
+        Self::parse(data.as_bytes(), fix, limits.unwrap_or_default().inner)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (path, fix = true, limits = None))]
+    fn from_file(path: std::path::PathBuf, fix: bool, limits: Option<Limits>) -> PyResult<Self> {
+        let data = std::fs::read(path)?;
+        Self::parse(&data, fix, limits.unwrap_or_default().inner)
+    }
+
+    #[getter]
+    fn width(&self) -> u16 {
+        self.inner.header.width
+    }
+
+    #[getter]
+    fn height(&self) -> u16 {
+        self.inner.header.height
+    }
+
+    /// Every action as an `Action` object, in replay order
+    #[getter]
+    fn actions(&self) -> Vec<Action> {
+        self.inner
+            .actions
+            .iter()
+            .enumerate()
+            .map(|(index, values)| Action::new(index, values))
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.actions.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PchFile(width={}, height={}, actions={})",
+            self.inner.header.width,
+            self.inner.header.height,
+            self.inner.actions.len()
+        )
+    }
+}
+
+impl PchFile {
+    fn parse(data: &[u8], fix: bool, limits: ReplayLimits) -> PyResult<Self> {
+        let mut inner = replay::PchFile::from_bytes_with_limits(data, &limits).map_err(to_py_err)?;
+        if fix {
+            inner.fix_actions();
+        }
+        Ok(PchFile { inner, limits })
+    }
+}
+
+/// One replay action. Fields that do not apply to the command are `None` or empty.
+#[pyclass(frozen)]
+#[derive(Clone)]
+pub struct Action {
+    #[pyo3(get)]
+    index: usize,
+    #[pyo3(get)]
+    command: String,
+    #[pyo3(get)]
+    layer: Option<usize>,
+    #[pyo3(get)]
+    color: Option<(u8, u8, u8, u8)>, // RGBA
+    #[pyo3(get)]
+    width: Option<f64>, // Pen width, or border width for fills
+    #[pyo3(get)]
+    line_type: Option<String>,
+    #[pyo3(get)]
+    points: Vec<(f64, f64)>,
+    #[pyo3(get)]
+    rect: Option<(f64, f64, f64, f64)>, // x, y, width, height of fills and clipboard actions
+    #[pyo3(get)]
+    text: Option<String>,
+    raw: Vec<ActionValue>,
+}
+
+impl Action {
+    fn new(index: usize, values: &[ActionValue]) -> Self {
+        let mut action = Action {
+            index,
+            command: match values.first() {
+                Some(ActionValue::String(command)) => command.clone(),
+                _ => String::new(),
+            },
+            layer: None,
+            color: None,
+            width: None,
+            line_type: None,
+            points: Vec::new(),
+            rect: None,
+            text: None,
+            raw: values.to_vec(),
+        };
+
+        match ActionKind::parse(values) {
+            Some(ActionKind::FreeHand(stroke) | ActionKind::Line(stroke) | ActionKind::Bezier(stroke)) => {
+                action.layer = Some(stroke.layer);
+                action.color = Some((stroke.color.r, stroke.color.g, stroke.color.b, stroke.color.a));
+                action.width = Some(stroke.width);
+                action.line_type = Some(format!("{:?}", stroke.line_type).to_lowercase());
+                action.points = stroke.points;
+            }
+            Some(ActionKind::Fill(fill)) => {
+                action.layer = Some(fill.layer);
+                action.color = Some((fill.color.r, fill.color.g, fill.color.b, fill.color.a));
+                action.width = Some(fill.width);
+                action.rect = Some((fill.x, fill.y, fill.w, fill.h));
+            }
+            Some(ActionKind::FloodFill { layer, x, y, color }) => {
+                // Flood fill colors are packed as 0xAABBGGRR
+                let [r, g, b, a] = color.to_le_bytes();
+                action.layer = Some(layer);
+                action.color = Some((r, g, b, a));
+                action.points = vec![(x, y)];
+            }
+            Some(ActionKind::Text(text)) => {
+                let [r, g, b, _] = text.color.to_le_bytes();
+                action.layer = Some(text.layer);
+                action.color = Some((r, g, b, (text.alpha.clamp(0.0, 1.0) * 255.0).round() as u8));
+                action.points = vec![(text.x, text.y)];
+                action.text = Some(text.text);
+            }
+            Some(ActionKind::Copy { layer, x, y, width, height } | ActionKind::Merge { layer, x, y, width, height }) => {
+                action.layer = Some(layer);
+                action.rect = Some((x, y, width, height));
+            }
+            Some(ActionKind::Paste { layer, x, y, width, height, dx, dy }) => {
+                action.layer = Some(layer);
+                action.rect = Some((x, y, width, height));
+                action.points = vec![(dx, dy)];
+            }
+            Some(ActionKind::EraseAll { layer }) => action.layer = Some(layer),
+            _ => {}
+        }
+
+        action
+    }
+}
+
+#[pymethods]
+impl Action {
+    /// The raw action array as stored in the file
+    #[getter]
+    fn values<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let items = self
+            .raw
+            .iter()
+            .map(|value| match value {
+                ActionValue::String(s) => Ok(s.into_pyobject(py)?.into_any()),
+                ActionValue::Number(n) => Ok(n.into_pyobject(py)?.into_any()),
+                ActionValue::Integer(i) => Ok(i.into_pyobject(py)?.into_any()),
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        PyList::new(py, items)
+    }
+
+    fn __repr__(&self) -> String {
+        match self.layer {
+            Some(layer) => format!("Action({}, '{}', layer={})", self.index, self.command, layer),
+            None => format!("Action({}, '{}')", self.index, self.command),
+        }
+    }
+}
+
+/// Replays a `PchFile` one action at a time.
+///
+/// Iterating yields the composite after each action as a `(height, width, 3)` uint8 array.
+#[pyclass]
+pub struct Renderer {
+    pch: replay::PchFile,
+    renderer: CanvasRenderer,
+    max_render_time: Option<Duration>, // From the limits the file was parsed with
+    position: usize, // Number of actions applied to the canvas
+}
+
+#[pymethods]
+impl Renderer {
+    #[new]
+    fn new(pch: &PchFile) -> Self {
+        let max_render_time = pch.limits.max_render_time;
+        let pch = pch.inner.clone();
+        let renderer = blank_renderer(&pch);
+        Renderer { pch, renderer, max_render_time, position: 0 }
+    }
+
+    #[getter]
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    #[getter]
+    fn layer_count(&self) -> usize {
+        self.renderer.canvas.layer_count()
+    }
+
+    /// Apply up to `count` further actions. Returns how many were applied.
+    /// Raises `ValueError` when the call takes longer than the render time limit.
+    #[pyo3(signature = (count = 1))]
+    fn step(&mut self, count: usize) -> PyResult<usize> {
+        self.renderer.set_time_limit(self.max_render_time);
+        let start = self.position;
+        let end = start.saturating_add(count).min(self.pch.actions.len());
+
+        for values in &self.pch.actions[start..end] {
+            self.renderer.step(values).map_err(to_py_err)?;
+            self.position += 1;
+        }
+
+        Ok(end - start)
+    }
+
+    /// Move to the state after `position` actions, replaying from the start when going back
+    fn seek(&mut self, position: usize) -> PyResult<()> {
+        let position = position.min(self.pch.actions.len());
+        if position < self.position {
+            self.renderer = blank_renderer(&self.pch);
+            self.position = 0;
+        }
+        self.step(position - self.position)?;
+        Ok(())
+    }
+
+    /// Visible layers composited over white, shape `(height, width, 3)`
+    fn composite<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let image = self.renderer.canvas.composite();
+        to_array(py, image.width(), image.height(), 3, image.into_raw())
+    }
+
+    /// Straight-alpha RGBA pixels of one layer, shape `(height, width, 4)`
+    fn layer<'py>(&self, py: Python<'py>, index: usize) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let image = self
+            .renderer
+            .canvas
+            .get_layer(index)
+            .ok_or_else(|| PyIndexError::new_err("Layer index out of range"))?;
+        to_array(py, image.width(), image.height(), 4, image.as_raw().clone())
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyArray3<u8>>>> {
+        if self.step(1)? == 0 {
+            return Ok(None);
+        }
+        self.composite(py).map(Some)
+    }
+}
+
+fn blank_renderer(pch: &replay::PchFile) -> CanvasRenderer {
+    let mut renderer = CanvasRenderer::new(pch.header.width as u32, pch.header.height as u32);
+    renderer.canvas.clear();
+    renderer
+}
+
+fn to_array(py: Python<'_>, width: u32, height: u32, channels: usize, data: Vec<u8>) -> PyResult<Bound<'_, PyArray3<u8>>> {
+    PyArray1::from_vec(py, data).reshape([height as usize, width as usize, channels])
+}
+
+#[pymodule]
+fn neo_replay(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Limits>()?;
+    m.add_class::<PchFile>()?;
+    m.add_class::<Action>()?;
+    m.add_class::<Renderer>()?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! JavaScript bindings for the replay renderer.
+//!
+//! Build with `wasm-pack build bindings/wasm --target web`. The renderer is the
+//! same one used on the server; text falls back to the bitmap font because
+//! system fonts are unavailable in the browser.
+
+use neo_replay_rs::limits::Limits;
+use neo_replay_rs::renderer::Renderer;
+use neo_replay_rs::PchFile;
+use wasm_bindgen::prelude::*;
+
+/// A loaded replay together with a renderer positioned at some action
+#[wasm_bindgen]
+pub struct Replay {
+    pch: PchFile,
+    renderer: Renderer,
+    position: usize, // Number of actions applied to the canvas
+}
+
+#[wasm_bindgen]
+impl Replay {
+    /// Parse a `.pch` file. `eraseAll` actions are normalized like the NEO player does.
+    /// Files over the default parsing limits are rejected, so a crafted file cannot
+    /// use up the tab's memory; there is no render time limit without a clock.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<Replay, JsError> {
+        let mut pch = PchFile::from_bytes_with_limits(bytes, &Limits::default()).map_err(|e| JsError::new(&e.to_string()))?;
+        pch.fix_actions();
+
+        let mut renderer = Renderer::new(pch.header.width as u32, pch.header.height as u32);
+        renderer.canvas.clear();
+
+        Ok(Replay { pch, renderer, position: 0 })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.renderer.canvas.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.renderer.canvas.height
+    }
+
+    #[wasm_bindgen(getter, js_name = actionCount)]
+    pub fn action_count(&self) -> usize {
+        self.pch.actions.len()
+    }
+
+    #[wasm_bindgen(getter, js_name = layerCount)]
+    pub fn layer_count(&self) -> usize {
+        self.renderer.canvas.layer_count()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Apply up to `count` further actions. Returns how many were applied.
+    pub fn step(&mut self, count: usize) -> Result<usize, JsError> {
+        let end = self.position.saturating_add(count).min(self.pch.actions.len());
+        let start = self.position;
+
+        for action in &self.pch.actions[start..end] {
+            self.renderer.step(action).map_err(|e| JsError::new(&e.to_string()))?;
+            self.position += 1;
+        }
+
+        Ok(end - start)
+    }
+
+    /// Move to the state after `position` actions, replaying from the start when going back
+    pub fn seek(&mut self, position: usize) -> Result<(), JsError> {
+        let position = position.min(self.pch.actions.len());
+        if position < self.position {
+            self.reset();
+        }
+        self.step(position - self.position)?;
+        Ok(())
+    }
+
+    /// Clear the canvas and return to the first action. Layer visibility and
+    /// opacity stay as set.
+    pub fn reset(&mut self) {
+        let previous = std::mem::replace(
+            &mut self.renderer,
+            Renderer::new(self.pch.header.width as u32, self.pch.header.height as u32),
+        );
+        self.renderer.canvas.clear();
+        self.renderer.canvas.visible = previous.canvas.visible;
+        self.renderer.canvas.opacity = previous.canvas.opacity;
+        self.position = 0;
+    }
+
+    /// RGBA pixels of one layer, suitable for `new ImageData(...)`
+    #[wasm_bindgen(js_name = layerRgba)]
+    pub fn layer_rgba(&self, layer: usize) -> Result<Vec<u8>, JsError> {
+        self.renderer
+            .canvas
+            .get_layer(layer)
+            .map(|image| image.as_raw().clone())
+            .ok_or_else(|| JsError::new("Layer index out of range"))
+    }
+
+    /// RGBA pixels of all visible layers composited over white
+    #[wasm_bindgen(js_name = compositeRgba)]
+    pub fn composite_rgba(&self) -> Vec<u8> {
+        self.renderer
+            .canvas
+            .composite()
+            .pixels()
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect()
+    }
+
+    #[wasm_bindgen(js_name = setLayerVisible)]
+    pub fn set_layer_visible(&mut self, layer: usize, visible: bool) {
+        self.renderer.canvas.set_visible(layer, visible);
+    }
+
+    #[wasm_bindgen(js_name = setLayerOpacity)]
+    pub fn set_layer_opacity(&mut self, layer: usize, opacity: f32) {
+        self.renderer.canvas.set_opacity(layer, opacity);
+    }
+}
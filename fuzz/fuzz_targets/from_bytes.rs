@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use neo_replay_rs::limits::Limits;
+use neo_replay_rs::PchFile;
+
+fuzz_target!(|data: &[u8]| {
+    // Default limits keep decompression bombs from exhausting the fuzzer's memory
+    if let Ok(mut pch) = PchFile::from_bytes_with_limits(data, &Limits::default()) {
+        pch.fix_actions();
+    }
+});
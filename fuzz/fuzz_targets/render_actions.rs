@@ -0,0 +1,48 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use neo_replay_rs::renderer::Renderer;
+use neo_replay_rs::ActionValue;
+
+const COMMANDS: &[&str] = &[
+    "clearCanvas", "eraseAll", "freeHand", "line", "bezier", "fill", "floodFill",
+    "text", "copy", "paste", "merge", "restore",
+];
+
+#[derive(Debug, Arbitrary)]
+enum Value {
+    Command(u8),
+    Number(f64),
+    Integer(i64),
+    Small(i8), // Coordinates, layers and sizes that land on the canvas
+    Text(String),
+}
+
+impl From<Value> for ActionValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Command(i) => ActionValue::String(COMMANDS[i as usize % COMMANDS.len()].to_string()),
+            Value::Number(n) => ActionValue::Number(n),
+            Value::Integer(n) => ActionValue::Integer(n),
+            Value::Small(n) => ActionValue::Number(n as f64),
+            Value::Text(s) => ActionValue::String(s),
+        }
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    width: u8,
+    height: u8,
+    actions: Vec<Vec<Value>>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut renderer = Renderer::new(input.width as u32, input.height as u32);
+    renderer.canvas.clear();
+    for action in input.actions {
+        let action: Vec<ActionValue> = action.into_iter().map(ActionValue::from).collect();
+        let _ = renderer.step(&action);
+    }
+});
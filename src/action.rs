@@ -0,0 +1,304 @@
+use crate::{ActionValue, Color, LineType, MaskType};
+use serde::{Deserialize, Serialize};
+
+/// Typed view of a single entry in `PchFile::actions`. Serializes as an object
+/// tagged with the NEO command name, e.g. `{"command": "floodFill", "layer": 0, ...}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "camelCase")]
+pub enum Action {
+    ClearCanvas,
+    EraseAll { layer: usize },
+    FreeHand(StrokeAction),
+    Line(StrokeAction),
+    Bezier(StrokeAction),
+    Fill(FillAction),
+    FloodFill { layer: usize, x: f64, y: f64, color: u32 },
+    Text(TextAction),
+    Copy { layer: usize, x: f64, y: f64, width: f64, height: f64 },
+    Paste { layer: usize, x: f64, y: f64, width: f64, height: f64, dx: f64, dy: f64 },
+    Merge { layer: usize, x: f64, y: f64, width: f64, height: f64 },
+    Restore,
+    #[serde(skip)]
+    Unknown(String),
+}
+
+/// Pen state and path shared by `freeHand`, `line` and `bezier`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrokeAction {
+    pub layer: usize,
+    pub color: Color,
+    pub mask: Color,
+    pub width: f64,
+    pub mask_type: MaskType,
+    pub line_type: LineType,
+    pub points: Vec<(f64, f64)>, // Bezier strokes hold start, two control points and end
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FillAction {
+    pub layer: usize,
+    pub color: Color,
+    pub mask: Color,
+    pub width: f64, // Border width for outline shapes
+    pub mask_type: MaskType,
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+    pub fill_type: u32, // 20-23, see `FillType`
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextAction {
+    pub layer: usize,
+    pub x: f64,
+    pub y: f64,
+    pub color: u32, // 0xBBGGRR
+    pub alpha: f64,
+    pub text: String,
+    pub size: String, // Kept as written, e.g. "27px"
+    #[serde(default)]
+    pub family: String, // May be empty to use the default font
+}
+
+impl Action {
+    /// Parse a raw action array. Returns `None` if a known command is malformed.
+    pub fn parse(values: &[ActionValue]) -> Option<Action> {
+        let command = match values.first()? {
+            ActionValue::String(s) => s.as_str(),
+            _ => return None,
+        };
+
+        let action = match command {
+            "clearCanvas" => Action::ClearCanvas,
+            "eraseAll" => Action::EraseAll { layer: layer_at(values, 1)? },
+            "freeHand" => Action::FreeHand(parse_stroke(values, 12)?),
+            "line" => Action::Line(parse_stroke(values, 16)?),
+            "bezier" => Action::Bezier(parse_stroke(values, 20)?),
+            "fill" => {
+                if values.len() < 16 {
+                    return None;
+                }
+                Action::Fill(FillAction {
+                    layer: layer_at(values, 1)?,
+                    color: color_at(values, 2)?,
+                    mask: mask_at(values, 6)?,
+                    width: number_at(values, 9)?,
+                    mask_type: MaskType::from(number_at(values, 10)? as i64),
+                    x: number_at(values, 11)?,
+                    y: number_at(values, 12)?,
+                    w: number_at(values, 13)?,
+                    h: number_at(values, 14)?,
+                    fill_type: number_at(values, 15)? as u32,
+                })
+            }
+            "floodFill" => Action::FloodFill {
+                layer: layer_at(values, 1)?,
+                x: number_at(values, 2)?,
+                y: number_at(values, 3)?,
+                color: number_at(values, 4)? as u32,
+            },
+            "text" => Action::Text(TextAction {
+                layer: layer_at(values, 1)?,
+                x: number_at(values, 2)?,
+                y: number_at(values, 3)?,
+                color: number_at(values, 4)? as u32,
+                alpha: number_at(values, 5)?,
+                text: string_at(values, 6)?,
+                size: match values.get(7)? {
+                    ActionValue::String(s) => s.clone(),
+                    ActionValue::Number(n) => n.to_string(),
+                    ActionValue::Integer(i) => i.to_string(),
+                },
+                family: values.get(8).and_then(as_string).unwrap_or_default(),
+            }),
+            "copy" => Action::Copy {
+                layer: layer_at(values, 1)?,
+                x: number_at(values, 2)?,
+                y: number_at(values, 3)?,
+                width: number_at(values, 4)?,
+                height: number_at(values, 5)?,
+            },
+            "paste" => Action::Paste {
+                layer: layer_at(values, 1)?,
+                x: number_at(values, 2)?,
+                y: number_at(values, 3)?,
+                width: number_at(values, 4)?,
+                height: number_at(values, 5)?,
+                dx: number_at(values, 6)?,
+                dy: number_at(values, 7)?,
+            },
+            "merge" => Action::Merge {
+                layer: layer_at(values, 1)?,
+                x: number_at(values, 2)?,
+                y: number_at(values, 3)?,
+                width: number_at(values, 4)?,
+                height: number_at(values, 5)?,
+            },
+            "restore" => Action::Restore,
+            other => Action::Unknown(other.to_string()),
+        };
+
+        Some(action)
+    }
+
+    pub fn command(&self) -> &str {
+        match self {
+            Action::ClearCanvas => "clearCanvas",
+            Action::EraseAll { .. } => "eraseAll",
+            Action::FreeHand(_) => "freeHand",
+            Action::Line(_) => "line",
+            Action::Bezier(_) => "bezier",
+            Action::Fill(_) => "fill",
+            Action::FloodFill { .. } => "floodFill",
+            Action::Text(_) => "text",
+            Action::Copy { .. } => "copy",
+            Action::Paste { .. } => "paste",
+            Action::Merge { .. } => "merge",
+            Action::Restore => "restore",
+            Action::Unknown(command) => command,
+        }
+    }
+
+    pub fn stroke(&self) -> Option<&StrokeAction> {
+        match self {
+            Action::FreeHand(stroke) | Action::Line(stroke) | Action::Bezier(stroke) => Some(stroke),
+            _ => None,
+        }
+    }
+
+    /// Layer the action draws on, if it targets one
+    pub fn layer(&self) -> Option<usize> {
+        match self {
+            Action::FreeHand(stroke) | Action::Line(stroke) | Action::Bezier(stroke) => Some(stroke.layer),
+            Action::Fill(fill) => Some(fill.layer),
+            Action::Text(text) => Some(text.layer),
+            Action::EraseAll { layer }
+            | Action::FloodFill { layer, .. }
+            | Action::Copy { layer, .. }
+            | Action::Paste { layer, .. }
+            | Action::Merge { layer, .. } => Some(*layer),
+            Action::ClearCanvas | Action::Restore | Action::Unknown(_) => None,
+        }
+    }
+
+    /// Raw action array in the layout `parse` reads. Arguments of `Restore` and
+    /// unknown commands are not kept, so only their command name is written.
+    pub fn to_values(&self) -> Vec<ActionValue> {
+        let mut values = vec![ActionValue::String(self.command().to_string())];
+        let mut push = |numbers: &[f64]| values.extend(numbers.iter().map(|&n| ActionValue::Number(n)));
+
+        match self {
+            Action::FreeHand(stroke) | Action::Line(stroke) | Action::Bezier(stroke) => {
+                push(&[stroke.layer as f64]);
+                push(&color_values(&stroke.color, &stroke.mask));
+                push(&[stroke.width, stroke.mask_type as i64 as f64, stroke.line_type as i64 as f64]);
+                for &(x, y) in &stroke.points {
+                    push(&[x, y]);
+                }
+            }
+            Action::Fill(fill) => {
+                push(&[fill.layer as f64]);
+                push(&color_values(&fill.color, &fill.mask));
+                push(&[fill.width, fill.mask_type as i64 as f64, fill.x, fill.y, fill.w, fill.h, fill.fill_type as f64]);
+            }
+            Action::FloodFill { layer, x, y, color } => push(&[*layer as f64, *x, *y, *color as f64]),
+            Action::Text(text) => {
+                push(&[text.layer as f64, text.x, text.y, text.color as f64, text.alpha]);
+                values.push(ActionValue::String(text.text.clone()));
+                values.push(ActionValue::String(text.size.clone()));
+                // Always written, as the renderer skips text actions without a family slot
+                values.push(ActionValue::String(text.family.clone()));
+            }
+            Action::EraseAll { layer } => push(&[*layer as f64]),
+            Action::Copy { layer, x, y, width, height } | Action::Merge { layer, x, y, width, height } => {
+                push(&[*layer as f64, *x, *y, *width, *height])
+            }
+            Action::Paste { layer, x, y, width, height, dx, dy } => push(&[*layer as f64, *x, *y, *width, *height, *dx, *dy]),
+            Action::ClearCanvas | Action::Restore | Action::Unknown(_) => {}
+        }
+        values
+    }
+}
+
+// Pen color as RGBA followed by the mask color as RGB
+fn color_values(color: &Color, mask: &Color) -> [f64; 7] {
+    [color.r, color.g, color.b, color.a, mask.r, mask.g, mask.b].map(f64::from)
+}
+
+impl StrokeAction {
+    /// Length of the drawn path in pixels. Bezier strokes use their control polygon.
+    pub fn path_length(&self) -> f64 {
+        self.points
+            .windows(2)
+            .map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1))
+            .sum()
+    }
+}
+
+fn parse_stroke(values: &[ActionValue], min_len: usize) -> Option<StrokeAction> {
+    if values.len() < min_len {
+        return None;
+    }
+
+    let points = values[12..]
+        .chunks_exact(2)
+        .map(|pair| Some((as_number(&pair[0])?, as_number(&pair[1])?)))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(StrokeAction {
+        layer: layer_at(values, 1)?,
+        color: color_at(values, 2)?,
+        mask: mask_at(values, 6)?,
+        width: number_at(values, 9)?,
+        mask_type: MaskType::from(number_at(values, 10)? as i64),
+        line_type: values.get(11).and_then(as_number).map_or(LineType::Pen, |n| LineType::from(n as i64)),
+        points,
+    })
+}
+
+fn color_at(values: &[ActionValue], index: usize) -> Option<Color> {
+    Some(Color {
+        r: number_at(values, index)? as u8,
+        g: number_at(values, index + 1)? as u8,
+        b: number_at(values, index + 2)? as u8,
+        a: number_at(values, index + 3)? as u8,
+    })
+}
+
+fn mask_at(values: &[ActionValue], index: usize) -> Option<Color> {
+    // Mask colors are stored as RGB only
+    Some(Color {
+        r: number_at(values, index)? as u8,
+        g: number_at(values, index + 1)? as u8,
+        b: number_at(values, index + 2)? as u8,
+        a: 255,
+    })
+}
+
+fn layer_at(values: &[ActionValue], index: usize) -> Option<usize> {
+    Some(number_at(values, index)? as usize)
+}
+
+fn number_at(values: &[ActionValue], index: usize) -> Option<f64> {
+    values.get(index).and_then(as_number)
+}
+
+fn string_at(values: &[ActionValue], index: usize) -> Option<String> {
+    values.get(index).and_then(as_string)
+}
+
+fn as_number(value: &ActionValue) -> Option<f64> {
+    match value {
+        ActionValue::Number(n) => Some(*n),
+        ActionValue::Integer(i) => Some(*i as f64),
+        ActionValue::String(_) => None,
+    }
+}
+
+fn as_string(value: &ActionValue) -> Option<String> {
+    match value {
+        ActionValue::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
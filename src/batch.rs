@@ -0,0 +1,203 @@
+//! Rendering whole directories of replays in parallel.
+//!
+//! Every `.pch` file below the input directory is rendered to a PNG of its final
+//! state at the same relative path below the output directory, and the outcome for
+//! each file is collected into a `BatchReport`.
+
+use crate::limits::Limits;
+use crate::renderer::Renderer;
+use crate::PchFile;
+use anyhow::{anyhow, Context, Result};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    pub scale: u32, // Re-render strokes at this many times the canvas size, 1 to `renderer::MAX_SCALE`
+    pub jobs: Option<usize>, // Worker threads, one per core when unset
+    pub limits: Limits, // Applied to every file; a file over a limit is reported as failed
+    pub skip_existing: bool, // Leave files whose output already exists alone
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self { scale: 1, jobs: None, limits: Limits::default(), skip_existing: false }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Rendered,
+    Skipped,
+    Failed,
+}
+
+impl FileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileStatus::Rendered => "rendered",
+            FileStatus::Skipped => "skipped",
+            FileStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub input: String, // Relative to the input directory
+    pub output: String, // Relative to the output directory
+    pub status: FileStatus,
+    pub error: Option<String>,
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+    pub actions: Option<usize>, // After splitting eraseAll actions
+    pub warnings: Vec<String>,
+    pub parse_ms: f64,
+    pub render_ms: f64, // Replaying and writing the PNG
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchReport {
+    pub rendered: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub elapsed_ms: f64,
+    pub files: Vec<FileReport>,
+}
+
+/// Paths of all `.pch` files below `dir`, relative to it and sorted
+pub fn find_replays(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in WalkDir::new(dir) {
+        let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+        let is_pch = entry.path().extension().is_some_and(|e| e.eq_ignore_ascii_case("pch"));
+        if entry.file_type().is_file() && is_pch {
+            paths.push(entry.path().strip_prefix(dir)?.to_path_buf());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Render every replay below `input_dir` into a mirrored tree of PNGs below `output_dir`
+pub fn render_directory(input_dir: &Path, output_dir: &Path, options: &BatchOptions) -> Result<BatchReport> {
+    let started = Instant::now();
+    let replays = find_replays(input_dir)?;
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(options.jobs.unwrap_or(0)).build()?;
+    let files: Vec<FileReport> = pool.install(|| {
+        replays
+            .par_iter()
+            .map(|relative| {
+                let mut report = render_file(&input_dir.join(relative), &output_dir.join(relative).with_extension("png"), options);
+                report.input = relative.to_string_lossy().into_owned();
+                report.output = relative.with_extension("png").to_string_lossy().into_owned();
+                report
+            })
+            .collect()
+    });
+
+    let count = |status| files.iter().filter(|file| file.status == status).count();
+    Ok(BatchReport {
+        rendered: count(FileStatus::Rendered),
+        skipped: count(FileStatus::Skipped),
+        failed: count(FileStatus::Failed),
+        elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+        files,
+    })
+}
+
+/// Render the final state of one replay to a PNG, creating parent directories as needed.
+/// Failures, including panics, are recorded in the report rather than returned.
+pub fn render_file(input: &Path, output: &Path, options: &BatchOptions) -> FileReport {
+    let mut report = FileReport {
+        input: input.to_string_lossy().into_owned(),
+        output: output.to_string_lossy().into_owned(),
+        status: FileStatus::Rendered,
+        error: None,
+        width: None,
+        height: None,
+        actions: None,
+        warnings: Vec::new(),
+        parse_ms: 0.0,
+        render_ms: 0.0,
+    };
+
+    if options.skip_existing && output.exists() {
+        report.status = FileStatus::Skipped;
+        return report;
+    }
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| render_into(&mut report, input, output, options)))
+        .unwrap_or_else(|_| Err(anyhow!("Renderer panicked")));
+    if let Err(error) = result {
+        report.status = FileStatus::Failed;
+        report.error = Some(format!("{:#}", error));
+    }
+    report
+}
+
+fn render_into(report: &mut FileReport, input: &Path, output: &Path, options: &BatchOptions) -> Result<()> {
+    let started = Instant::now();
+    let data = fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    let mut pch = PchFile::from_bytes_with_limits(&data, &options.limits)?;
+    pch.fix_actions();
+    report.width = Some(pch.header.width);
+    report.height = Some(pch.header.height);
+    report.actions = Some(pch.actions.len());
+    report.parse_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let started = Instant::now();
+    let (width, height) = (pch.header.width as u32, pch.header.height as u32);
+    options.limits.check_canvas(width.saturating_mul(options.scale), height.saturating_mul(options.scale))?;
+    let mut renderer = Renderer::with_scale(width, height, options.scale)?;
+    renderer.set_time_limit(options.limits.max_render_time);
+    let result = renderer.render_to(&pch, pch.actions.len());
+    report.warnings = std::mem::take(&mut renderer.warnings);
+    result?;
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    renderer.canvas.composite().save(output).with_context(|| format!("Failed to write {}", output.display()))?;
+    report.render_ms = started.elapsed().as_secs_f64() * 1000.0;
+    Ok(())
+}
+
+impl BatchReport {
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// One row per file; warnings are joined with "; "
+    pub fn save_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record([
+            "input", "output", "status", "error", "width", "height", "actions", "warnings", "parse_ms", "render_ms",
+        ])?;
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        for file in &self.files {
+            writer.write_record([
+                file.input.clone(),
+                file.output.clone(),
+                file.status.as_str().to_string(),
+                optional(file.error.clone()),
+                optional(file.width.map(|w| w.to_string())),
+                optional(file.height.map(|h| h.to_string())),
+                optional(file.actions.map(|a| a.to_string())),
+                file.warnings.join("; "),
+                format!("{:.1}", file.parse_ms),
+                format!("{:.1}", file.render_ms),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
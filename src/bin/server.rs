@@ -0,0 +1,307 @@
+use anyhow::{anyhow, bail, Result};
+use image::imageops::{self, FilterType};
+use image::{ImageOutputFormat, RgbImage};
+use neo_replay_rs::{
+    PchFile,
+    contact_sheet::{self, Sampling},
+    export::webp::{self, WebpFrame},
+    limits::{LimitExceeded, Limits},
+    pacing::{PacingModel, Playback},
+    renderer::{Renderer, NEO_LAYERS},
+};
+use serde::Serialize;
+use std::env;
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const USAGE: &str = "Usage: neo-replay-server [--addr HOST:PORT] [--workers N] [--max-body BYTES] [--max-time SECS]
+                         [--max-area PIXELS] [--max-actions N]
+
+Endpoints (POST the .pch file as the request body):
+  /render                          Final image as PNG
+  /thumbnail?width=PX              Downscaled final image as PNG (default 160)
+  /animation?frames=N&speed=X      Animated WebP of N sampled states (or duration=SECS)
+  /info                            Dimensions, action count and estimated duration as JSON
+  GET /health                      Liveness check";
+
+const MAX_ANIMATION_FRAMES: usize = 300;
+// Browsers treat shorter frame durations as 100ms
+const MIN_FRAME_MS: u32 = 20;
+const FINAL_FRAME_MS: u32 = 2000;
+
+struct Config {
+    addr: String,
+    workers: usize,
+    max_body: usize, // Largest accepted .pch upload in bytes
+    limits: Limits, // Parsing and rendering bounds per request
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            addr: "127.0.0.1:8080".to_string(),
+            workers: 4,
+            max_body: 8 * 1024 * 1024,
+            limits: Limits {
+                max_render_time: Some(Duration::from_secs(30)),
+                ..Limits::default()
+            },
+        }
+    }
+}
+
+/// Failure reported to the client with an HTTP status
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    /// Map a library error, reporting exceeded limits separately from bad input
+    fn from_error(status: u16, context: &str, error: anyhow::Error) -> Self {
+        match error.downcast_ref::<LimitExceeded>() {
+            Some(LimitExceeded::RenderTime(_)) => Self::new(503, error.to_string()),
+            Some(_) => Self::new(413, error.to_string()),
+            None => Self::new(status, format!("{}: {}", context, error)),
+        }
+    }
+}
+
+struct Reply {
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+#[derive(Serialize)]
+struct Info {
+    width: u16,
+    height: u16,
+    actions: usize,
+    layers: usize,
+    duration: f64, // Estimated drawing time in seconds at normal speed
+}
+
+/// A parsed upload and the rendering budget it was accepted with
+struct Job {
+    pch: PchFile,
+    max_render_time: Option<Duration>,
+}
+
+impl Job {
+    fn renderer(&self) -> Renderer {
+        let mut renderer = Renderer::new(self.pch.header.width as u32, self.pch.header.height as u32);
+        renderer.canvas.clear();
+        renderer.set_time_limit(self.max_render_time);
+        renderer
+    }
+
+    /// Apply actions `from..to`
+    fn replay(&self, renderer: &mut Renderer, from: usize, to: usize) -> Result<(), ApiError> {
+        for values in &self.pch.actions[from..to] {
+            renderer
+                .step(values)
+                .map_err(|e| ApiError::from_error(422, "Failed to render", e))?;
+        }
+        Ok(())
+    }
+
+    fn final_image(&self) -> Result<RgbImage, ApiError> {
+        let mut renderer = self.renderer();
+        self.replay(&mut renderer, 0, self.pch.actions.len())?;
+        Ok(renderer.canvas.composite())
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Config> {
+    let mut config = Config::default();
+    for pair in args.chunks(2) {
+        match pair {
+            [flag, value] if flag == "--addr" => config.addr = value.clone(),
+            [flag, value] if flag == "--workers" => config.workers = value.parse::<usize>()?.max(1),
+            [flag, value] if flag == "--max-body" => config.max_body = value.parse()?,
+            [flag, value] if flag == "--max-time" => {
+                config.limits.max_render_time = Some(Duration::from_secs_f64(value.parse()?))
+            }
+            [flag, value] if flag == "--max-area" => config.limits.max_canvas_area = value.parse()?,
+            [flag, value] if flag == "--max-actions" => config.limits.max_actions = value.parse()?,
+            _ => bail!("Unexpected argument: {}", pair[0]),
+        }
+    }
+    Ok(config)
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let config = parse_args(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(1);
+    });
+
+    let server = Arc::new(Server::http(&config.addr).map_err(|e| anyhow!("Failed to listen on {}: {}", config.addr, e))?);
+    // Print the bound address so callers can pass port 0 and discover the real port
+    println!("Listening on http://{}", server.server_addr());
+
+    let config = Arc::new(config);
+    let workers: Vec<_> = (0..config.workers)
+        .map(|_| {
+            let (server, config) = (Arc::clone(&server), Arc::clone(&config));
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle(request, &config);
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+fn handle(mut request: Request, config: &Config) {
+    let started = Instant::now();
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    let result = match (&method, path) {
+        (Method::Get, "/health") => Ok(Reply { content_type: "text/plain", body: b"ok\n".to_vec() }),
+        (Method::Post, "/render" | "/thumbnail" | "/animation" | "/info") => {
+            read_job(&mut request, config).and_then(|job| match path {
+                "/render" => render(&job),
+                "/thumbnail" => thumbnail(&job, query),
+                "/animation" => animation(&job, query),
+                _ => info(&job),
+            })
+        }
+        (_, "/health" | "/render" | "/thumbnail" | "/animation" | "/info") => Err(ApiError::new(405, "Method not allowed")),
+        _ => Err(ApiError::new(404, "Not found")),
+    };
+
+    let (status, content_type, body) = match result {
+        Ok(reply) => (200, reply.content_type, reply.body),
+        Err(error) => (error.status, "text/plain", format!("{}\n", error.message).into_bytes()),
+    };
+    println!("{} {} -> {} in {}ms", method, url, status, started.elapsed().as_millis());
+
+    let header = Header::from_bytes("Content-Type", content_type).expect("Valid header");
+    let response = Response::from_data(body).with_status_code(status).with_header(header);
+    if let Err(e) = request.respond(response) {
+        eprintln!("Failed to send response: {}", e);
+    }
+}
+
+fn read_job(request: &mut Request, config: &Config) -> Result<Job, ApiError> {
+    let too_large = || ApiError::new(413, format!("Upload exceeds {} bytes", config.max_body));
+    if request.body_length().is_some_and(|length| length > config.max_body) {
+        return Err(too_large());
+    }
+
+    let mut data = Vec::new();
+    request
+        .as_reader()
+        .take(config.max_body as u64 + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| ApiError::new(400, format!("Failed to read upload: {}", e)))?;
+    if data.len() > config.max_body {
+        return Err(too_large());
+    }
+
+    let mut pch = PchFile::from_bytes_with_limits(&data, &config.limits)
+        .map_err(|e| ApiError::from_error(400, "Invalid PCH file", e))?;
+    pch.fix_actions();
+    Ok(Job { pch, max_render_time: config.limits.max_render_time })
+}
+
+fn query_param<T: FromStr>(query: &str, name: &str) -> Result<Option<T>, ApiError> {
+    let Some(value) = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(key, value)| (key == name).then_some(value))
+    else {
+        return Ok(None);
+    };
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| ApiError::new(400, format!("Invalid value for {}: {}", name, value)))
+}
+
+fn png(image: &RgbImage) -> Result<Reply, ApiError> {
+    let mut buffer = Cursor::new(Vec::new());
+    image
+        .write_to(&mut buffer, ImageOutputFormat::Png)
+        .map_err(|e| ApiError::new(500, format!("Failed to encode PNG: {}", e)))?;
+    Ok(Reply { content_type: "image/png", body: buffer.into_inner() })
+}
+
+fn render(job: &Job) -> Result<Reply, ApiError> {
+    png(&job.final_image()?)
+}
+
+fn thumbnail(job: &Job, query: &str) -> Result<Reply, ApiError> {
+    let image = job.final_image()?;
+    let width = query_param(query, "width")?.unwrap_or(160).clamp(1, image.width().max(1));
+    if width == image.width() {
+        return png(&image);
+    }
+    let height = ((image.height() as u64 * width as u64) / image.width() as u64).max(1) as u32;
+    png(&imageops::resize(&image, width, height, FilterType::Triangle))
+}
+
+fn animation(job: &Job, query: &str) -> Result<Reply, ApiError> {
+    let frames = query_param(query, "frames")?.unwrap_or(60).clamp(1, MAX_ANIMATION_FRAMES);
+    let playback = match (query_param(query, "speed")?, query_param(query, "duration")?) {
+        (_, Some(duration)) => Playback::TargetDuration(duration),
+        (speed, None) => Playback::Speed(speed.unwrap_or(1.0)),
+    };
+
+    let timeline = PacingModel::default().timeline(&job.pch, Some(2.0)).apply(playback);
+    // Seconds from the start at which the state after `count` actions is reached
+    let time_at = |count: usize| {
+        count
+            .checked_sub(1)
+            .and_then(|i| timeline.entries.get(i))
+            .map_or(0.0, |entry| entry.start + entry.duration)
+    };
+
+    let points = contact_sheet::sample_points(&job.pch, Sampling::Even(frames));
+    let mut renderer = job.renderer();
+    let mut replayed = 0;
+    let mut webp_frames = Vec::with_capacity(points.len());
+
+    for (i, &count) in points.iter().enumerate() {
+        job.replay(&mut renderer, replayed, count)?;
+        replayed = count;
+
+        let duration = match points.get(i + 1) {
+            Some(&next) => (((time_at(next) - time_at(count)) * 1000.0).round() as u32).max(MIN_FRAME_MS),
+            None => FINAL_FRAME_MS,
+        };
+        webp_frames.push(WebpFrame { image: renderer.canvas.composite(), duration });
+    }
+
+    let body = webp::encode_animated_webp(&webp_frames).map_err(|e| ApiError::new(422, e.to_string()))?;
+    Ok(Reply { content_type: "image/webp", body })
+}
+
+fn info(job: &Job) -> Result<Reply, ApiError> {
+    let info = Info {
+        width: job.pch.header.width,
+        height: job.pch.header.height,
+        actions: job.pch.actions.len(),
+        layers: NEO_LAYERS,
+        duration: PacingModel::default().timeline(&job.pch, Some(2.0)).total(),
+    };
+    let body = serde_json::to_vec(&info).map_err(|e| ApiError::new(500, e.to_string()))?;
+    Ok(Reply { content_type: "application/json", body })
+}
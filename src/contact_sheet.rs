@@ -0,0 +1,179 @@
+use crate::action::Action;
+use crate::renderer::Renderer;
+use crate::PchFile;
+use anyhow::{bail, Result};
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+use serde::Serialize;
+
+const CAPTION_HEIGHT: u32 = 10;
+// Largest sheet in pixels, about 800 MB as RGB
+const MAX_SHEET_AREA: u64 = 16384 * 16384;
+
+/// Which replay states end up on the sheet
+#[derive(Debug, Clone, Copy)]
+pub enum Sampling {
+    /// N states evenly spaced over the replay, always including the final one
+    Even(usize),
+    /// The state after every freeHand, line and bezier stroke
+    PerStroke,
+}
+
+#[derive(Debug, Clone)]
+pub struct SheetOptions {
+    pub sampling: Sampling,
+    pub columns: Option<usize>, // Defaults to a roughly square grid
+    pub tile_width: u32, // Tiles are downscaled to at most this width, never upscaled
+    pub padding: u32,
+    pub captions: bool, // Print the action index under each tile
+}
+
+/// Position of every tile in the sheet image, serialized as the JSON atlas
+#[derive(Debug, Clone, Serialize)]
+pub struct Atlas {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: usize,
+    pub rows: usize,
+    pub tiles: Vec<AtlasTile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AtlasTile {
+    pub frame: usize,
+    pub action_index: usize, // Number of actions replayed for this tile
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Default for SheetOptions {
+    fn default() -> Self {
+        Self {
+            sampling: Sampling::Even(16),
+            columns: None,
+            tile_width: 160,
+            padding: 4,
+            captions: true,
+        }
+    }
+}
+
+/// Action counts at which the sheet samples the replay, in ascending order
+pub fn sample_points(pch: &PchFile, sampling: Sampling) -> Vec<usize> {
+    let total = pch.actions.len();
+    match sampling {
+        Sampling::Even(count) => {
+            let count = count.max(1);
+            let mut points: Vec<usize> = (1..=count).map(|i| i * total / count).collect();
+            points.dedup();
+            points
+        }
+        Sampling::PerStroke => pch
+            .actions
+            .iter()
+            .enumerate()
+            .filter(|(_, values)| Action::parse(values).is_some_and(|action| action.stroke().is_some()))
+            .map(|(index, _)| index + 1)
+            .collect(),
+    }
+}
+
+/// Render sampled replay states and tile them into a single image
+pub fn contact_sheet(pch: &PchFile, options: &SheetOptions) -> Result<(RgbImage, Atlas)> {
+    let points = sample_points(pch, options.sampling);
+    if points.is_empty() {
+        bail!("Replay has no frames to sample");
+    }
+
+    let width = pch.header.width as u32;
+    let height = pch.header.height as u32;
+    let tile_width = options.tile_width.clamp(1, width.max(1));
+    let tile_height = ((height as u64 * tile_width as u64) / width.max(1) as u64).max(1) as u32;
+    let caption_height = if options.captions { CAPTION_HEIGHT } else { 0 };
+
+    let columns = options
+        .columns
+        .unwrap_or_else(|| (points.len() as f64).sqrt().ceil() as usize)
+        .clamp(1, points.len());
+    let rows = points.len().div_ceil(columns);
+    let cell_width = tile_width.checked_add(options.padding);
+    let cell_height = tile_height.checked_add(caption_height).and_then(|h| h.checked_add(options.padding));
+    let (Some(cell_width), Some(cell_height)) = (cell_width, cell_height) else {
+        bail!("Padding of {} is too large", options.padding);
+    };
+    let sheet_size = |count: usize, cell: u32| {
+        u32::try_from(count).ok()?.checked_mul(cell)?.checked_add(options.padding)
+    };
+    let (Some(sheet_width), Some(sheet_height)) = (sheet_size(columns, cell_width), sheet_size(rows, cell_height)) else {
+        bail!("Contact sheet of {} columns and {} rows is too large", columns, rows);
+    };
+    if sheet_width as u64 * sheet_height as u64 > MAX_SHEET_AREA {
+        bail!(
+            "Contact sheet of {}x{} exceeds {} pixels; use fewer frames, fewer columns or a smaller tile width",
+            sheet_width,
+            sheet_height,
+            MAX_SHEET_AREA
+        );
+    }
+
+    let mut sheet = RgbImage::from_pixel(sheet_width, sheet_height, Rgb([255, 255, 255]));
+    let mut tiles = Vec::with_capacity(points.len());
+    let mut renderer = Renderer::new(width, height);
+    renderer.canvas.clear();
+    let mut replayed = 0;
+
+    for (frame, &action_index) in points.iter().enumerate() {
+        for values in &pch.actions[replayed..action_index] {
+            renderer.step(values)?;
+        }
+        replayed = action_index;
+
+        let x = options.padding + (frame % columns) as u32 * cell_width;
+        let y = options.padding + (frame / columns) as u32 * cell_height;
+        let composite = renderer.canvas.composite();
+        let thumbnail = if tile_width == width {
+            composite
+        } else {
+            imageops::resize(&composite, tile_width, tile_height, FilterType::Triangle)
+        };
+        imageops::replace(&mut sheet, &thumbnail, x as i64, y as i64);
+
+        if options.captions {
+            draw_caption(&mut sheet, x, y + tile_height + 1, &action_index.to_string());
+        }
+
+        tiles.push(AtlasTile { frame, action_index, x, y });
+    }
+
+    let atlas = Atlas {
+        width: sheet_width,
+        height: sheet_height,
+        tile_width,
+        tile_height,
+        columns,
+        rows,
+        tiles,
+    };
+    Ok((sheet, atlas))
+}
+
+fn draw_caption(image: &mut RgbImage, x: u32, y: u32, text: &str) {
+    let font = Renderer::simple_font_data();
+    let mut char_x = x;
+
+    for ch in text.chars() {
+        if let Some(bitmap) = font.get(&ch) {
+            for (row, &byte) in bitmap.iter().enumerate() {
+                for col in 0..8 {
+                    let (px, py) = (char_x + col, y + row as u32);
+                    if (byte >> (7 - col)) & 1 == 1 && px < image.width() && py < image.height() {
+                        image.put_pixel(px, py, Rgb([0, 0, 0]));
+                    }
+                }
+            }
+        }
+        char_x += 7;
+    }
+}
@@ -0,0 +1,121 @@
+//! Text dumps of the action list for inspecting, hand-editing and diffing replays.
+//!
+//! Each action is written as an object with named fields from the typed model in
+//! `action`. Actions the typed model cannot reproduce exactly (malformed arrays,
+//! unknown commands, extra values) are written as `{"raw": [...]}` instead, so
+//! importing a dump always gives back the same actions.
+//!
+//! JSON dumps are an object with the canvas size and an `actions` array holding one
+//! action per line. NDJSON dumps put the canvas size on the first line and one
+//! action on each following line.
+
+use crate::action::Action;
+use crate::{whole_numbers_as_integers, ActionValue, PchFile, PchHeader};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpHeader {
+    pub width: u16,
+    pub height: u16,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub reserved: [u8; 4], // Unused header bytes, only written when set
+}
+
+#[derive(Deserialize)]
+struct JsonDump {
+    #[serde(flatten)]
+    header: DumpHeader,
+    actions: Vec<Value>,
+}
+
+fn is_zero(bytes: &[u8; 4]) -> bool {
+    bytes == &[0; 4]
+}
+
+/// Dump entry for one raw action on a single line: the typed form when it
+/// round-trips, otherwise the values
+pub fn action_to_json(values: &[ActionValue]) -> Result<String> {
+    if let Some(action) = Action::parse(values) {
+        if !matches!(action, Action::Unknown(_)) && action.to_values() == values {
+            return Ok(serde_json::to_string(&action)?);
+        }
+    }
+    let raw = whole_numbers_as_integers(values);
+    Ok(serde_json::to_string(&serde_json::json!({ "raw": raw }))?)
+}
+
+/// Raw action for a dump entry written by `action_to_json` or edited by hand
+pub fn action_from_value(value: Value) -> Result<Vec<ActionValue>> {
+    if let Some(raw) = value.get("raw") {
+        return Ok(serde_json::from_value(raw.clone())?);
+    }
+    let action: Action = serde_json::from_value(value)?;
+    Ok(action.to_values())
+}
+
+pub fn to_json(pch: &PchFile) -> Result<String> {
+    let mut out = format!("{{\n  \"width\": {},\n  \"height\": {},\n", pch.header.width, pch.header.height);
+    if !is_zero(&pch.header.reserved) {
+        out.push_str(&format!("  \"reserved\": {},\n", serde_json::to_string(&pch.header.reserved)?));
+    }
+    out.push_str("  \"actions\": [");
+    for (i, values) in pch.actions.iter().enumerate() {
+        out.push_str(if i == 0 { "\n    " } else { ",\n    " });
+        out.push_str(&action_to_json(values)?);
+    }
+    out.push_str(if pch.actions.is_empty() { "]\n}\n" } else { "\n  ]\n}\n" });
+    Ok(out)
+}
+
+pub fn to_ndjson(pch: &PchFile) -> Result<String> {
+    let mut out = serde_json::to_string(&dump_header(pch))?;
+    out.push('\n');
+    for values in &pch.actions {
+        out.push_str(&action_to_json(values)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+pub fn from_json(text: &str) -> Result<PchFile> {
+    let dump: JsonDump = serde_json::from_str(text).context("Invalid JSON dump")?;
+    let actions = dump
+        .actions
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| action_from_value(value).with_context(|| format!("Invalid action {}", i)))
+        .collect::<Result<_>>()?;
+    Ok(pch_file(dump.header, actions))
+}
+
+pub fn from_ndjson(text: &str) -> Result<PchFile> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let Some((first_index, first)) = lines.next() else {
+        bail!("Empty NDJSON dump");
+    };
+    let header: DumpHeader =
+        serde_json::from_str(first).with_context(|| format!("Invalid header on line {}", first_index + 1))?;
+
+    let actions = lines
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .map_err(anyhow::Error::from)
+                .and_then(action_from_value)
+                .with_context(|| format!("Invalid action on line {}", i + 1))
+        })
+        .collect::<Result<_>>()?;
+    Ok(pch_file(header, actions))
+}
+
+fn dump_header(pch: &PchFile) -> DumpHeader {
+    DumpHeader { width: pch.header.width, height: pch.header.height, reserved: pch.header.reserved }
+}
+
+fn pch_file(header: DumpHeader, actions: Vec<Vec<ActionValue>>) -> PchFile {
+    PchFile {
+        header: PchHeader { magic: *b"NEO ", width: header.width, height: header.height, reserved: header.reserved },
+        actions,
+    }
+}
@@ -0,0 +1,74 @@
+//! Cutting and joining replays.
+//!
+//! Action indices count actions after `PchFile::fix_actions`, the same numbering
+//! used for rendered frames and `export --at`. Every result is normalized that way
+//! and keeps the canvas size of its input.
+
+use crate::{ActionValue, PchFile};
+use anyhow::{bail, Result};
+use std::ops::Range;
+
+fn normalized(pch: &PchFile) -> PchFile {
+    let mut pch = pch.clone();
+    pch.fix_actions();
+    pch
+}
+
+/// Keep the first `end` actions
+pub fn trim(pch: &PchFile, end: usize) -> PchFile {
+    let mut pch = normalized(pch);
+    pch.actions.truncate(end);
+    pch
+}
+
+/// Split into the first `index` actions and the rest. The second part starts
+/// from a blank canvas, so on its own it only shows what was drawn after the cut.
+pub fn split_at(pch: &PchFile, index: usize) -> (PchFile, PchFile) {
+    let mut head = normalized(pch);
+    let tail_actions = head.actions.split_off(index.min(head.actions.len()));
+    let tail = PchFile { header: head.header.clone(), actions: tail_actions };
+    (head, tail)
+}
+
+/// Remove actions `range`. Later copy/paste and flood fills may then act on
+/// different pixels than in the original.
+pub fn drop_range(pch: &PchFile, range: Range<usize>) -> Result<PchFile> {
+    let mut pch = normalized(pch);
+    if range.start > range.end || range.end > pch.actions.len() {
+        bail!("Range {}..{} is outside the {} actions", range.start, range.end, pch.actions.len());
+    }
+    pch.actions.drain(range);
+    Ok(pch)
+}
+
+/// Join sessions of the same drawing into one replay. Continued sessions open with
+/// `restore` actions that reload the previous picture; those are dropped, as the
+/// preceding sessions already draw it.
+pub fn concat(parts: &[PchFile]) -> Result<PchFile> {
+    let Some(first) = parts.first() else {
+        bail!("Nothing to concatenate");
+    };
+
+    let mut joined = normalized(first);
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        let (width, height) = (part.header.width, part.header.height);
+        if (width, height) != (joined.header.width, joined.header.height) {
+            bail!(
+                "Replay {} is {}x{}, but the first is {}x{}",
+                i + 1,
+                width,
+                height,
+                joined.header.width,
+                joined.header.height
+            );
+        }
+        let part = normalized(part);
+        let leading_restores = part.actions.iter().take_while(|action| is_restore(action)).count();
+        joined.actions.extend(part.actions.into_iter().skip(leading_restores));
+    }
+    Ok(joined)
+}
+
+fn is_restore(action: &[ActionValue]) -> bool {
+    matches!(action.first(), Some(ActionValue::String(command)) if command == "restore")
+}
@@ -0,0 +1,105 @@
+use crate::contact_sheet::{self, Sampling, SheetOptions};
+use crate::pacing::{PacingModel, Playback};
+use crate::PchFile;
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+const INDEX_HTML: &str = include_str!("player/index.html");
+const PLAYER_JS: &str = include_str!("player/player.js");
+
+// Browsers refuse to decode very large images; keep the sprite sheet below this on both axes
+const MAX_SPRITE_SIZE: u32 = 8192;
+
+#[derive(Debug, Clone)]
+pub struct PlayerOptions {
+    pub frames: usize, // Replay states stored in the sprite sheet
+    pub playback: Playback,
+    pub max_idle: Option<f64>, // Passed to `PacingModel::timeline`
+}
+
+#[derive(Serialize)]
+struct ReplayData {
+    width: u32,
+    height: u32,
+    duration: f64,
+    sprites: &'static str,
+    frames: Vec<FrameData>,
+}
+
+#[derive(Serialize)]
+struct FrameData {
+    action_index: usize,
+    time: f64, // Seconds from the start at which this frame is shown
+    x: u32,
+    y: u32,
+}
+
+impl Default for PlayerOptions {
+    fn default() -> Self {
+        Self {
+            frames: 120,
+            playback: Playback::Speed(1.0),
+            max_idle: Some(2.0),
+        }
+    }
+}
+
+/// Write a self-contained player (index.html, player.js, replay.js and a sprite
+/// sheet of sampled frames) into `dir`. It can be served from static storage or
+/// opened straight from disk.
+pub fn write_player_bundle<P: AsRef<Path>>(pch: &PchFile, dir: P, options: &PlayerOptions) -> Result<()> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    let width = (pch.header.width as u32).max(1);
+    let height = (pch.header.height as u32).max(1);
+    let max_frames = ((MAX_SPRITE_SIZE / width) * (MAX_SPRITE_SIZE / height)).max(1) as usize;
+    let frames = options.frames.clamp(1, max_frames);
+    let columns = ((MAX_SPRITE_SIZE / width).max(1) as usize).min(frames);
+
+    let (sprites, atlas) = contact_sheet::contact_sheet(
+        pch,
+        &SheetOptions {
+            sampling: Sampling::Even(frames),
+            columns: Some(columns),
+            tile_width: width,
+            padding: 0,
+            captions: false,
+        },
+    )?;
+    sprites.save(dir.join("frames.png"))?;
+
+    // A frame appears once the last action it contains has finished drawing
+    let timeline = PacingModel::default()
+        .timeline(pch, options.max_idle)
+        .apply(options.playback);
+    let frames: Vec<FrameData> = atlas
+        .tiles
+        .iter()
+        .map(|tile| FrameData {
+            action_index: tile.action_index,
+            time: tile
+                .action_index
+                .checked_sub(1)
+                .and_then(|i| timeline.entries.get(i))
+                .map_or(0.0, |entry| entry.start + entry.duration),
+            x: tile.x,
+            y: tile.y,
+        })
+        .collect();
+
+    let data = ReplayData {
+        width,
+        height,
+        duration: frames.last().map_or(0.0, |frame| frame.time),
+        sprites: "frames.png",
+        frames,
+    };
+
+    fs::write(dir.join("replay.js"), format!("window.NEO_REPLAY = {};\n", serde_json::to_string(&data)?))?;
+    fs::write(dir.join("index.html"), INDEX_HTML)?;
+    fs::write(dir.join("player.js"), PLAYER_JS)?;
+    Ok(())
+}
@@ -0,0 +1,26 @@
+pub mod html;
+pub mod ora;
+pub mod psd;
+pub mod svg;
+pub mod webp;
+
+use anyhow::Result;
+use image::{ImageOutputFormat, RgbImage, RgbaImage};
+use std::io::Cursor;
+
+fn encode_png_rgba(image: &RgbaImage) -> Result<Vec<u8>> {
+    let mut buffer = Cursor::new(Vec::new());
+    image.write_to(&mut buffer, ImageOutputFormat::Png)?;
+    Ok(buffer.into_inner())
+}
+
+fn encode_png_rgb(image: &RgbImage) -> Result<Vec<u8>> {
+    let mut buffer = Cursor::new(Vec::new());
+    image.write_to(&mut buffer, ImageOutputFormat::Png)?;
+    Ok(buffer.into_inner())
+}
+
+/// Display name of a canvas layer in exported documents
+fn layer_name(layer: usize) -> String {
+    format!("Layer {}", layer)
+}
@@ -0,0 +1,78 @@
+use super::{encode_png_rgb, encode_png_rgba, layer_name};
+use crate::renderer::Canvas;
+use anyhow::Result;
+use image::imageops::{self, FilterType};
+use std::fs::File;
+use std::io::{Seek, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Write the canvas layers as an OpenRaster (.ora) archive
+pub fn write_ora<W: Write + Seek>(canvas: &Canvas, writer: W) -> Result<()> {
+    let mut zip = ZipWriter::new(writer);
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype must be the first entry and stored uncompressed
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"image/openraster")?;
+
+    zip.start_file("stack.xml", deflated)?;
+    zip.write_all(stack_xml(canvas).as_bytes())?;
+
+    // PNG data is already compressed
+    for (layer, image) in canvas.layers.iter().enumerate() {
+        zip.start_file(format!("data/layer{}.png", layer), stored)?;
+        zip.write_all(&encode_png_rgba(image)?)?;
+    }
+
+    let merged = canvas.composite();
+    zip.start_file("mergedimage.png", stored)?;
+    zip.write_all(&encode_png_rgb(&merged)?)?;
+
+    let scale = (THUMBNAIL_SIZE as f64 / canvas.width.max(canvas.height).max(1) as f64).min(1.0);
+    let thumbnail = imageops::resize(
+        &merged,
+        ((canvas.width as f64 * scale).round() as u32).max(1),
+        ((canvas.height as f64 * scale).round() as u32).max(1),
+        FilterType::Triangle,
+    );
+    zip.start_file("Thumbnails/thumbnail.png", stored)?;
+    zip.write_all(&encode_png_rgb(&thumbnail)?)?;
+
+    zip.finish()?;
+    Ok(())
+}
+
+pub fn save_ora<P: AsRef<Path>>(canvas: &Canvas, path: P) -> Result<()> {
+    write_ora(canvas, File::create(path)?)
+}
+
+fn stack_xml(canvas: &Canvas) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version='1.0' encoding='UTF-8'?>\n");
+    xml.push_str(&format!(
+        "<image version=\"0.0.5\" w=\"{}\" h=\"{}\" xres=\"72\" yres=\"72\">\n  <stack>\n",
+        canvas.width, canvas.height
+    ));
+
+    // OpenRaster lists the topmost layer first
+    for &layer in canvas.order.iter().rev() {
+        if layer >= canvas.layer_count() {
+            continue;
+        }
+        xml.push_str(&format!(
+            "    <layer name=\"{}\" src=\"data/layer{}.png\" x=\"0\" y=\"0\" opacity=\"{:.3}\" visibility=\"{}\" composite-op=\"svg:src-over\"/>\n",
+            layer_name(layer),
+            layer,
+            canvas.get_opacity(layer),
+            if canvas.is_visible(layer) { "visible" } else { "hidden" },
+        ));
+    }
+
+    xml.push_str("  </stack>\n</image>\n");
+    xml
+}
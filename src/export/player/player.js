@@ -0,0 +1,95 @@
+(function () {
+  "use strict";
+
+  var replay = window.NEO_REPLAY;
+  var canvas = document.getElementById("canvas");
+  var context = canvas.getContext("2d");
+  var playButton = document.getElementById("play");
+  var seek = document.getElementById("seek");
+  var speedSelect = document.getElementById("speed");
+  var timeLabel = document.getElementById("time");
+
+  var sprites = new Image();
+  var time = 0;
+  var playing = false;
+  var lastTick = null;
+  var shownFrame = null;
+
+  canvas.width = replay.width;
+  canvas.height = replay.height;
+  seek.max = replay.duration;
+
+  // Index of the last frame whose time has been reached, -1 before the first one
+  function frameAt(t) {
+    var low = 0, high = replay.frames.length - 1, found = -1;
+    while (low <= high) {
+      var mid = (low + high) >> 1;
+      if (replay.frames[mid].time <= t) {
+        found = mid;
+        low = mid + 1;
+      } else {
+        high = mid - 1;
+      }
+    }
+    return found;
+  }
+
+  function draw() {
+    var index = frameAt(time);
+    if (index !== shownFrame) {
+      shownFrame = index;
+      context.fillStyle = "#fff";
+      context.fillRect(0, 0, replay.width, replay.height);
+      if (index >= 0) {
+        var frame = replay.frames[index];
+        context.drawImage(sprites, frame.x, frame.y, replay.width, replay.height,
+          0, 0, replay.width, replay.height);
+      }
+    }
+    seek.value = time;
+    timeLabel.textContent = time.toFixed(1) + " / " + replay.duration.toFixed(1) + "s";
+  }
+
+  function tick(now) {
+    if (!playing) {
+      return;
+    }
+    if (lastTick !== null) {
+      time += (now - lastTick) / 1000 * parseFloat(speedSelect.value);
+    }
+    lastTick = now;
+    if (time >= replay.duration) {
+      time = replay.duration;
+      setPlaying(false);
+    }
+    draw();
+    requestAnimationFrame(tick);
+  }
+
+  function setPlaying(value) {
+    playing = value;
+    lastTick = null;
+    playButton.textContent = playing ? "Pause" : "Play";
+    if (playing) {
+      requestAnimationFrame(tick);
+    }
+  }
+
+  playButton.addEventListener("click", function () {
+    if (!playing && time >= replay.duration) {
+      time = 0;
+    }
+    setPlaying(!playing);
+  });
+
+  seek.addEventListener("input", function () {
+    time = parseFloat(seek.value);
+    draw();
+  });
+
+  sprites.onload = function () {
+    draw();
+    setPlaying(true);
+  };
+  sprites.src = replay.sprites;
+})();
@@ -0,0 +1,126 @@
+use super::layer_name;
+use crate::renderer::Canvas;
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const PSD_MAX_DIMENSION: u32 = 30_000;
+const COMPRESSION_RAW: u16 = 0;
+const COLOR_MODE_RGB: u16 = 3;
+const FLAG_HIDDEN: u8 = 0x02;
+
+/// Write the canvas as a layered Photoshop document.
+///
+/// Every canvas layer becomes an RGBA raster layer with its name, opacity and
+/// visibility; the flattened composite is stored as the document image.
+pub fn write_psd<W: Write>(canvas: &Canvas, mut writer: W) -> Result<()> {
+    if canvas.width == 0 || canvas.height == 0 || canvas.width > PSD_MAX_DIMENSION || canvas.height > PSD_MAX_DIMENSION {
+        bail!("Canvas size {}x{} cannot be stored as PSD", canvas.width, canvas.height);
+    }
+
+    let mut out = Vec::new();
+
+    // File header
+    out.extend_from_slice(b"8BPS");
+    put_u16(&mut out, 1); // Version
+    out.extend_from_slice(&[0; 6]); // Reserved
+    put_u16(&mut out, 3); // Channels of the merged image
+    put_u32(&mut out, canvas.height);
+    put_u32(&mut out, canvas.width);
+    put_u16(&mut out, 8); // Bits per channel
+    put_u16(&mut out, COLOR_MODE_RGB);
+
+    put_u32(&mut out, 0); // Color mode data
+    put_u32(&mut out, 0); // Image resources
+
+    let layer_info = layer_info(canvas);
+    put_u32(&mut out, layer_info.len() as u32 + 8);
+    put_u32(&mut out, layer_info.len() as u32);
+    out.extend_from_slice(&layer_info);
+    put_u32(&mut out, 0); // Global layer mask info
+
+    // Merged image data, planar RGB
+    let merged = canvas.composite();
+    put_u16(&mut out, COMPRESSION_RAW);
+    for channel in 0..3 {
+        out.extend(merged.pixels().map(|p| p[channel]));
+    }
+
+    writer.write_all(&out)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn save_psd<P: AsRef<Path>>(canvas: &Canvas, path: P) -> Result<()> {
+    write_psd(canvas, BufWriter::new(File::create(path)?))
+}
+
+fn layer_info(canvas: &Canvas) -> Vec<u8> {
+    // Layer records are listed bottom to top
+    let layers: Vec<usize> = canvas.order.iter().copied().filter(|&l| l < canvas.layer_count()).collect();
+    let channel_length = canvas.width * canvas.height + 2; // Compression marker plus raw data
+
+    let mut info = Vec::new();
+    put_u16(&mut info, layers.len() as u16);
+
+    for &layer in &layers {
+        put_u32(&mut info, 0); // Top
+        put_u32(&mut info, 0); // Left
+        put_u32(&mut info, canvas.height); // Bottom
+        put_u32(&mut info, canvas.width); // Right
+
+        put_u16(&mut info, 4);
+        for channel_id in [-1i16, 0, 1, 2] {
+            info.extend_from_slice(&channel_id.to_be_bytes());
+            put_u32(&mut info, channel_length);
+        }
+
+        info.extend_from_slice(b"8BIMnorm");
+        info.push((canvas.get_opacity(layer) * 255.0).round() as u8);
+        info.push(0); // Clipping
+        info.push(if canvas.is_visible(layer) { 0 } else { FLAG_HIDDEN });
+        info.push(0); // Filler
+
+        let mut extra = Vec::new();
+        put_u32(&mut extra, 0); // Layer mask data
+        put_u32(&mut extra, 0); // Blending ranges
+        extra.extend(pascal_string(&layer_name(layer)));
+        put_u32(&mut info, extra.len() as u32);
+        info.extend(extra);
+    }
+
+    // Channel image data, in the same order as the records
+    for &layer in &layers {
+        let image = &canvas.layers[layer];
+        for channel in [3, 0, 1, 2] {
+            put_u16(&mut info, COMPRESSION_RAW);
+            info.extend(image.pixels().map(|p| p[channel]));
+        }
+    }
+
+    // Section length must be even
+    if info.len() % 2 == 1 {
+        info.push(0);
+    }
+    info
+}
+
+/// Pascal string padded to a multiple of four bytes, as layer names require
+fn pascal_string(text: &str) -> Vec<u8> {
+    let bytes: Vec<u8> = text.bytes().take(255).collect();
+    let mut out = vec![bytes.len() as u8];
+    out.extend(bytes);
+    while out.len() % 4 != 0 {
+        out.push(0);
+    }
+    out
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
@@ -0,0 +1,282 @@
+use super::encode_png_rgba;
+use crate::action::{Action, FillAction, StrokeAction, TextAction};
+use crate::renderer::Renderer;
+use crate::{Color, LineType, PchFile};
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::{Rgba, RgbaImage};
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Reconstruct the first `count` actions of `pch` as an SVG document.
+///
+/// Strokes become paths, rectangle and ellipse fills become shapes and text stays
+/// text. Eraser strokes are applied as masks over everything drawn before them on
+/// the layer. Flood fills, pastes and merges have no vector form and are embedded
+/// as PNG images of the pixels they changed.
+pub fn to_svg(pch: &PchFile, count: usize) -> Result<String> {
+    let width = pch.header.width as u32;
+    let height = pch.header.height as u32;
+    let mut renderer = Renderer::new(width, height);
+    let layer_count = renderer.canvas.layer_count();
+
+    let mut layers: Vec<String> = vec![String::new(); layer_count];
+    let mut defs = String::new();
+    let mut mask_count = 0;
+
+    for values in pch.actions.iter().take(count) {
+        let Some(action) = Action::parse(values) else {
+            // No vector form, but the renderer still draws what it can, which later
+            // raster captures must see
+            renderer.step(values)?;
+            continue;
+        };
+
+        // Actions without a vector form are captured from the raster renderer
+        let before = match action {
+            Action::FloodFill { layer, .. } | Action::Paste { layer, .. } if layer < layer_count => {
+                Some(renderer.canvas.layers[layer].clone())
+            }
+            _ => None,
+        };
+        renderer.step(values)?;
+
+        match &action {
+            Action::ClearCanvas => layers.iter_mut().for_each(String::clear),
+            Action::EraseAll { layer } => {
+                if let Some(body) = layers.get_mut(*layer) {
+                    body.clear();
+                }
+            }
+            Action::FreeHand(stroke) | Action::Line(stroke) | Action::Bezier(stroke) => {
+                let Some(body) = layers.get_mut(stroke.layer) else {
+                    continue;
+                };
+                let (d, offset) = (stroke_path(&action, stroke), pixel_offset(stroke.width));
+                if stroke.line_type == LineType::Eraser {
+                    mask_count += 1;
+                    let _ = writeln!(
+                        defs,
+                        "<mask id=\"erase{}\" maskUnits=\"userSpaceOnUse\" x=\"0\" y=\"0\" width=\"{}\" height=\"{}\">\
+                         <rect width=\"{}\" height=\"{}\" fill=\"white\"/>\
+                         <path d=\"{}\" transform=\"translate({} {})\" fill=\"none\" stroke=\"black\" stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/></mask>",
+                        mask_count, width, height, width, height, d, offset, offset, stroke.width.max(1.0)
+                    );
+                    *body = format!("<g mask=\"url(#erase{})\">{}</g>\n", mask_count, body);
+                } else {
+                    let _ = writeln!(
+                        body,
+                        "<path d=\"{}\" transform=\"translate({} {})\" fill=\"none\" stroke=\"{}\" stroke-opacity=\"{:.3}\" stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+                        d, offset, offset, rgb(&stroke.color), stroke.color.a as f64 / 255.0, stroke.width.max(1.0)
+                    );
+                }
+            }
+            Action::Fill(fill) => {
+                if let Some(body) = layers.get_mut(fill.layer) {
+                    body.push_str(&fill_shape(fill));
+                }
+            }
+            Action::Text(text) => {
+                if let Some(body) = layers.get_mut(text.layer) {
+                    body.push_str(&text_element(text));
+                }
+            }
+            Action::FloodFill { layer, .. } | Action::Paste { layer, .. } => {
+                if let (Some(before), Some(body)) = (&before, layers.get_mut(*layer)) {
+                    if let Some(image) = changed_pixels_image(before, &renderer.canvas.layers[*layer])? {
+                        body.push_str(&image);
+                    }
+                }
+            }
+            Action::Merge { .. } | Action::Restore => {
+                // Both layers of a merge change; replace them with their raster state
+                for (layer, body) in layers.iter_mut().enumerate() {
+                    *body = raster_image(&renderer.canvas.layers[layer], 0, 0)?.unwrap_or_default();
+                }
+            }
+            Action::Copy { .. } | Action::Unknown(_) => {}
+        }
+    }
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        width, height, width, height
+    );
+    if !defs.is_empty() {
+        let _ = write!(svg, "<defs>\n{}</defs>\n", defs);
+    }
+    let _ = writeln!(svg, "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>", width, height);
+
+    for &layer in &renderer.canvas.order {
+        let Some(body) = layers.get(layer) else {
+            continue;
+        };
+        let visibility = if renderer.canvas.is_visible(layer) { "" } else { " visibility=\"hidden\"" };
+        let _ = write!(
+            svg,
+            "<g id=\"layer{}\" opacity=\"{:.3}\"{}>\n{}</g>\n",
+            layer,
+            renderer.canvas.get_opacity(layer),
+            visibility,
+            body
+        );
+    }
+    svg.push_str("</svg>\n");
+
+    Ok(svg)
+}
+
+pub fn save_svg<P: AsRef<Path>>(pch: &PchFile, count: usize, path: P) -> Result<()> {
+    std::fs::write(path, to_svg(pch, count)?)?;
+    Ok(())
+}
+
+// Brush dabs of odd widths are centered on the middle of a pixel
+fn pixel_offset(width: f64) -> f64 {
+    let d = width.max(1.0).floor();
+    d / 2.0 - (d / 2.0).floor()
+}
+
+fn stroke_path(action: &Action, stroke: &StrokeAction) -> String {
+    let mut d = String::new();
+    let points = &stroke.points;
+    let Some(&(x0, y0)) = points.first() else {
+        return d;
+    };
+
+    let _ = write!(d, "M{} {}", x0, y0);
+    match action {
+        Action::Bezier(_) if points.len() >= 4 => {
+            let _ = write!(
+                d,
+                " C{} {} {} {} {} {}",
+                points[1].0, points[1].1, points[2].0, points[2].1, points[3].0, points[3].1
+            );
+        }
+        _ if points.len() == 1 => {
+            // Single dab, draw as a zero-length segment so round caps make a dot
+            let _ = write!(d, " L{} {}", x0, y0);
+        }
+        _ => {
+            for &(x, y) in &points[1..] {
+                let _ = write!(d, " L{} {}", x, y);
+            }
+        }
+    }
+    d
+}
+
+fn fill_shape(fill: &FillAction) -> String {
+    let paint = rgb(&fill.color);
+    let alpha = fill.color.a as f64 / 255.0;
+    let border = fill.width.max(1.0);
+
+    match fill.fill_type {
+        20 => format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"{}\" stroke-opacity=\"{:.3}\" stroke-width=\"{}\"/>\n",
+            fill.x + border / 2.0,
+            fill.y + border / 2.0,
+            (fill.w - border).max(0.0),
+            (fill.h - border).max(0.0),
+            paint,
+            alpha,
+            border
+        ),
+        21 => format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"{:.3}\"/>\n",
+            fill.x, fill.y, fill.w, fill.h, paint, alpha
+        ),
+        22 => format!(
+            "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" fill=\"none\" stroke=\"{}\" stroke-opacity=\"{:.3}\" stroke-width=\"{}\"/>\n",
+            fill.x + fill.w / 2.0,
+            fill.y + fill.h / 2.0,
+            ((fill.w - border) / 2.0).max(0.0),
+            ((fill.h - border) / 2.0).max(0.0),
+            paint,
+            alpha,
+            border
+        ),
+        23 => format!(
+            "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" fill=\"{}\" fill-opacity=\"{:.3}\"/>\n",
+            fill.x + fill.w / 2.0,
+            fill.y + fill.h / 2.0,
+            fill.w / 2.0,
+            fill.h / 2.0,
+            paint,
+            alpha
+        ),
+        _ => String::new(),
+    }
+}
+
+fn text_element(text: &TextAction) -> String {
+    // Text colors are stored as 0xBBGGRR
+    let color = Color {
+        r: (text.color & 0xff) as u8,
+        g: ((text.color >> 8) & 0xff) as u8,
+        b: ((text.color >> 16) & 0xff) as u8,
+        a: 255,
+    };
+    let family = if text.family.is_empty() { "sans-serif" } else { text.family.as_str() };
+    format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"{}\" fill=\"{}\" fill-opacity=\"{:.3}\" dominant-baseline=\"hanging\">{}</text>\n",
+        text.x,
+        text.y,
+        escape(&text.size),
+        escape(family),
+        rgb(&color),
+        text.alpha.clamp(0.0, 1.0),
+        escape(&text.text)
+    )
+}
+
+/// Embed only the pixels that differ between `before` and `after`, cropped to their bounds
+fn changed_pixels_image(before: &RgbaImage, after: &RgbaImage) -> Result<Option<String>> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in after.enumerate_pixels() {
+        if before.get_pixel(x, y) != pixel {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    if min_x > max_x {
+        return Ok(None);
+    }
+
+    let region = RgbaImage::from_fn(max_x - min_x + 1, max_y - min_y + 1, |x, y| {
+        let (px, py) = (min_x + x, min_y + y);
+        let pixel = after.get_pixel(px, py);
+        if before.get_pixel(px, py) != pixel { *pixel } else { Rgba([0, 0, 0, 0]) }
+    });
+    raster_image(&region, min_x, min_y)
+}
+
+fn raster_image(image: &RgbaImage, x: u32, y: u32) -> Result<Option<String>> {
+    if image.pixels().all(|p| p[3] == 0) {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" style=\"image-rendering:pixelated\" href=\"data:image/png;base64,{}\"/>\n",
+        x,
+        y,
+        image.width(),
+        image.height(),
+        BASE64.encode(encode_png_rgba(image)?)
+    )))
+}
+
+fn rgb(color: &Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,89 @@
+use anyhow::{bail, Result};
+use image::RgbImage;
+use image_webp::{ColorType, WebPEncoder};
+use std::io::Write;
+use std::path::Path;
+
+// Frame durations and canvas dimensions are stored as 24-bit fields
+const MAX_FIELD: u32 = (1 << 24) - 1;
+
+/// A frame of an animated WebP and how long it is shown, in milliseconds
+#[derive(Debug, Clone)]
+pub struct WebpFrame {
+    pub image: RgbImage,
+    pub duration: u32,
+}
+
+/// Encode frames as a looping, lossless animated WebP.
+///
+/// Every frame must have the size of the first. Frames are stored whole rather
+/// than as changed regions, which keeps the encoder simple at some cost in size.
+pub fn encode_animated_webp(frames: &[WebpFrame]) -> Result<Vec<u8>> {
+    let Some(first) = frames.first() else {
+        bail!("Animation has no frames");
+    };
+    let (width, height) = first.image.dimensions();
+    if width == 0 || height == 0 || width > MAX_FIELD + 1 || height > MAX_FIELD + 1 {
+        bail!("Invalid animation size {}x{}", width, height);
+    }
+
+    let mut chunks = Vec::new();
+
+    // VP8X header with only the animation flag set
+    let mut vp8x = vec![0x02, 0, 0, 0];
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+    write_chunk(&mut chunks, b"VP8X", &vp8x)?;
+
+    // White background, loop forever
+    write_chunk(&mut chunks, b"ANIM", &[255, 255, 255, 255, 0, 0])?;
+
+    for frame in frames {
+        if frame.image.dimensions() != (width, height) {
+            bail!("Animation frames differ in size");
+        }
+
+        let mut anmf = Vec::new();
+        anmf.extend_from_slice(&[0; 6]); // Frame offset
+        anmf.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        anmf.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        anmf.extend_from_slice(&frame.duration.min(MAX_FIELD).to_le_bytes()[..3]);
+        anmf.push(0x02); // Overwrite instead of alpha-blending, keep on dispose
+        anmf.extend_from_slice(&encode_vp8l_chunk(&frame.image)?);
+        write_chunk(&mut chunks, b"ANMF", &anmf)?;
+    }
+
+    let mut webp = Vec::with_capacity(chunks.len() + 12);
+    webp.extend_from_slice(b"RIFF");
+    webp.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+    webp.extend_from_slice(b"WEBP");
+    webp.extend_from_slice(&chunks);
+    Ok(webp)
+}
+
+pub fn save_animated_webp<P: AsRef<Path>>(frames: &[WebpFrame], path: P) -> Result<()> {
+    std::fs::write(path, encode_animated_webp(frames)?)?;
+    Ok(())
+}
+
+/// Lossless bitstream of a single image, as a complete `VP8L` chunk
+fn encode_vp8l_chunk(image: &RgbImage) -> Result<Vec<u8>> {
+    let mut still = Vec::new();
+    WebPEncoder::new(&mut still).encode(image.as_raw(), image.width(), image.height(), ColorType::Rgb8)?;
+
+    // A still image without metadata is "RIFF" size "WEBP" followed by the VP8L chunk
+    match still.get(12..) {
+        Some(chunk) if chunk.starts_with(b"VP8L") => Ok(chunk.to_vec()),
+        _ => bail!("Unexpected WebP encoder output"),
+    }
+}
+
+fn write_chunk<W: Write>(w: &mut W, fourcc: &[u8; 4], data: &[u8]) -> Result<()> {
+    w.write_all(fourcc)?;
+    w.write_all(&(data.len() as u32).to_le_bytes())?;
+    w.write_all(data)?;
+    if data.len() % 2 == 1 {
+        w.write_all(&[0])?;
+    }
+    Ok(())
+}
@@ -1,11 +1,28 @@
+pub mod action;
+#[cfg(feature = "batch")]
+pub mod batch;
+pub mod contact_sheet;
+pub mod dump;
+pub mod edit;
+pub mod export;
+pub mod limits;
+pub mod lzstring;
+pub mod overlay;
+pub mod pacing;
+pub mod recover;
 pub mod renderer;
+pub mod stats;
+pub mod upscale;
+pub mod validate;
+pub mod verify;
 
 use anyhow::{bail, Result};
+use limits::{LimitExceeded, Limits};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ActionValue {
     String(String),
@@ -13,7 +30,7 @@ pub enum ActionValue {
     Integer(i64),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -35,7 +52,8 @@ pub struct PchFile {
     pub actions: Vec<Vec<ActionValue>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LineType {
     None = 0,
     Pen = 1,
@@ -54,7 +72,8 @@ pub enum AlphaType {
     Fill,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MaskType {
     None = 0,
     Normal = 1,
@@ -87,67 +106,124 @@ impl PchFile {
     }
 
     pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_bytes_with_limits(data, &Limits::unlimited())
+    }
+
+    /// Parse a file from an untrusted source. Fails with `LimitExceeded` as soon as
+    /// the data goes over one of the parsing bounds in `limits`.
+    pub fn from_bytes_with_limits(data: &[u8], limits: &Limits) -> Result<Self> {
+        let header = Self::parse_header(data, limits)?;
+
+        // Decompress the lz-string payload, stopping early if it grows too large
+        let compressed = &data[12..];
+        let decompressed = lzstring::decompress_from_uint8_array(compressed, limits.max_decompressed_size)?;
+
+        // Convert Vec<u16> to String - properly handle Unicode
+        let decompressed_string: String = decompressed.into_iter()
+            .filter_map(|c| std::char::from_u32(c as u32))
+            .collect();
+        
+        // Parse JSON
+        let actions: Vec<Vec<ActionValue>> = serde_json::from_str(&decompressed_string)?;
+        check_action_limits(&actions, limits)?;
+
+        Ok(PchFile { header, actions })
+    }
+
+    /// Header fields as stored, without checking them
+    pub(crate) fn read_header(data: &[u8]) -> Result<PchHeader> {
         if data.len() < 12 {
             bail!("PCH file too short");
         }
 
-        // Parse header
-        let header = PchHeader {
+        Ok(PchHeader {
             magic: [data[0], data[1], data[2], data[3]],
             width: u16::from_le_bytes([data[4], data[5]]),
             height: u16::from_le_bytes([data[6], data[7]]),
             reserved: [data[8], data[9], data[10], data[11]],
-        };
+        })
+    }
+
+    /// Header of a `.pch` file, checked against the canvas bounds in `limits`
+    pub(crate) fn parse_header(data: &[u8], limits: &Limits) -> Result<PchHeader> {
+        let header = Self::read_header(data)?;
 
         // Verify magic
         if &header.magic != b"NEO " {
             bail!("Invalid PCH file magic");
         }
+        limits.check_canvas(header.width as u32, header.height as u32)?;
+        Ok(header)
+    }
 
-        // Decompress data using lz_str
-        let compressed = &data[12..];
-        let decompressed = lz_str::decompress_from_uint8_array(compressed)
-            .ok_or_else(|| anyhow::anyhow!("Failed to decompress PCH data"))?;
+    /// Encode as a `.pch` file the way NEO writes it
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let actions: Vec<Vec<ActionValue>> = self.actions.iter().map(|action| whole_numbers_as_integers(action)).collect();
+        let json = serde_json::to_string(&actions)?;
 
-        // Convert Vec<u16> to String - properly handle Unicode
-        let decompressed_string: String = decompressed.into_iter()
-            .filter_map(|c| std::char::from_u32(c as u32))
-            .collect();
-        
-        // Parse JSON
-        let actions: Vec<Vec<ActionValue>> = serde_json::from_str(&decompressed_string)?;
+        let mut data = Vec::with_capacity(12 + json.len());
+        data.extend_from_slice(&self.header.magic);
+        data.extend_from_slice(&self.header.width.to_le_bytes());
+        data.extend_from_slice(&self.header.height.to_le_bytes());
+        data.extend_from_slice(&self.header.reserved);
+        data.extend_from_slice(&lz_str::compress_to_uint8_array(json.as_str()));
+        Ok(data)
+    }
 
-        Ok(PchFile { header, actions })
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
     }
 
     pub fn fix_actions(&mut self) {
-        // Fix eraseAll actions as per original JavaScript logic
-        let mut i = 0;
-        while i < self.actions.len() {
-            let action = &self.actions[i];
-            
-            // Find "eraseAll" in the action
-            let mut erase_all_index = None;
-            for (idx, value) in action.iter().enumerate() {
-                if let ActionValue::String(s) = value {
-                    if s == "eraseAll" && idx > 0 {
-                        erase_all_index = Some(idx);
-                        break;
-                    }
-                }
-            }
+        // Fix eraseAll actions as per original JavaScript logic: the eraseAll part of an
+        // action runs before the rest. Rebuilt in one pass to stay linear on large files.
+        let mut fixed = Vec::with_capacity(self.actions.len());
+        for mut action in std::mem::take(&mut self.actions) {
+            let erase_all_index = action
+                .iter()
+                .enumerate()
+                .skip(1)
+                .find(|(_, value)| matches!(value, ActionValue::String(s) if s == "eraseAll"))
+                .map(|(idx, _)| idx);
 
             if let Some(index) = erase_all_index {
-                let before = action[..index].to_vec();
-                let after = action[index..].to_vec();
-                
-                self.actions[i] = before;
-                self.actions.insert(i, after);
-                i += 1; // Skip the newly inserted action
+                let after = action.split_off(index);
+                fixed.push(after);
             }
-            i += 1;
+            fixed.push(action);
+        }
+        self.actions = fixed;
+    }
+}
+
+pub(crate) fn check_action_limits(actions: &[Vec<ActionValue>], limits: &Limits) -> Result<()> {
+    if actions.len() > limits.max_actions {
+        return Err(LimitExceeded::ActionCount { count: actions.len(), max: limits.max_actions }.into());
+    }
+    for (index, action) in actions.iter().enumerate() {
+        let is_stroke = matches!(
+            action.first(),
+            Some(ActionValue::String(command)) if matches!(command.as_str(), "freeHand" | "line" | "bezier")
+        );
+        let count = action.len().saturating_sub(12) / 2;
+        if is_stroke && count > limits.max_points_per_action {
+            return Err(LimitExceeded::PointCount { index, count, max: limits.max_points_per_action }.into());
         }
     }
+    Ok(())
+}
+
+/// Copy of `action` whose whole numbers serialize without a fraction, like
+/// JavaScript's JSON.stringify writes them
+pub(crate) fn whole_numbers_as_integers(action: &[ActionValue]) -> Vec<ActionValue> {
+    action
+        .iter()
+        .map(|value| match *value {
+            ActionValue::Number(n) if n.fract() == 0.0 && n.abs() < 2f64.powi(53) => ActionValue::Integer(n as i64),
+            ref other => other.clone(),
+        })
+        .collect()
 }
 
 impl Default for DrawingState {
@@ -0,0 +1,62 @@
+use std::time::Duration;
+use thiserror::Error;
+
+/// Bounds on the work a single replay may cause, for files from untrusted sources.
+///
+/// `Limits::default()` is sized for uploads to a public board; `Limits::unlimited()`
+/// matches the behaviour of `PchFile::from_bytes`.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub max_decompressed_size: usize, // In UTF-16 code units of the decompressed JSON
+    pub max_canvas_area: u64, // Width times height in pixels
+    pub max_actions: usize,
+    pub max_points_per_action: usize, // Coordinate pairs in a single freeHand/line/bezier stroke
+    pub max_render_time: Option<Duration>, // Checked during rendering by `Renderer::set_time_limit`
+}
+
+/// Returned, wrapped in `anyhow::Error`, when input goes over a `Limits` bound.
+/// Use `error.downcast_ref::<LimitExceeded>()` to tell it apart from malformed input.
+#[derive(Debug, Clone, Error)]
+pub enum LimitExceeded {
+    #[error("Decompressed replay is larger than {0} characters")]
+    DecompressedSize(usize),
+    #[error("Canvas of {width}x{height} exceeds {max} pixels")]
+    CanvasArea { width: u32, height: u32, max: u64 },
+    #[error("Replay has {count} actions, more than {max}")]
+    ActionCount { count: usize, max: usize },
+    #[error("Action {index} has {count} points, more than {max}")]
+    PointCount { index: usize, count: usize, max: usize },
+    #[error("Rendering took longer than {0:?}")]
+    RenderTime(Duration),
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_decompressed_size: 32 * 1024 * 1024,
+            max_canvas_area: 4096 * 4096,
+            max_actions: 1_000_000,
+            max_points_per_action: 100_000,
+            max_render_time: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Self {
+            max_decompressed_size: usize::MAX,
+            max_canvas_area: u64::MAX,
+            max_actions: usize::MAX,
+            max_points_per_action: usize::MAX,
+            max_render_time: None,
+        }
+    }
+
+    pub fn check_canvas(&self, width: u32, height: u32) -> Result<(), LimitExceeded> {
+        if width as u64 * height as u64 > self.max_canvas_area {
+            return Err(LimitExceeded::CanvasArea { width, height, max: self.max_canvas_area });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+//! lz-string decompression with a cap on the output size.
+//!
+//! NEO stores replays with lz-string's `compressToUint8Array`. A few hundred bytes
+//! of crafted input can expand to gigabytes, so unlike the `lz_str` crate this
+//! decoder stops as soon as the output would exceed a limit, and keeps its
+//! dictionary as ranges into the output rather than separate copies.
+
+use crate::limits::LimitExceeded;
+use anyhow::{bail, Result};
+
+const U8_CODE: u32 = 0;
+const U16_CODE: u32 = 1;
+const CLOSE_CODE: u32 = 2;
+
+#[derive(Clone, Copy)]
+enum Entry {
+    Char(u16),
+    Range { start: usize, len: usize }, // Slice of the output decoded so far
+}
+
+/// Reads bits most significant first from big-endian 16-bit characters
+struct BitReader<'a> {
+    data: &'a [u8],
+    next: usize, // Byte offset of the next character
+    value: u16,
+    position: u16,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let mut reader = BitReader { data, next: 0, value: 0, position: 1 << 15 };
+        reader.value = reader.next_char()?;
+        Some(reader)
+    }
+
+    fn next_char(&mut self) -> Option<u16> {
+        let bytes = self.data.get(self.next..self.next + 2)?;
+        self.next += 2;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let bit = self.value & self.position != 0;
+        self.position >>= 1;
+        if self.position == 0 {
+            self.position = 1 << 15;
+            self.value = self.next_char()?;
+        }
+        Some(bit)
+    }
+
+    fn read_bits(&mut self, count: u32) -> Option<u32> {
+        let mut result = 0;
+        for bit in 0..count {
+            result |= (self.read_bit()? as u32) << bit;
+        }
+        Some(result)
+    }
+}
+
+/// Decompress data written by `compressToUint8Array` into UTF-16 code units,
+/// failing with `LimitExceeded::DecompressedSize` beyond `max_len` units
+pub fn decompress_from_uint8_array(data: &[u8], max_len: usize) -> Result<Vec<u16>> {
+    if data.len() % 2 == 1 {
+        bail!("Failed to decompress PCH data");
+    }
+    let mut output = Vec::new();
+    if !decompress(data, max_len, &mut output)? {
+        bail!("Failed to decompress PCH data");
+    }
+    Ok(output)
+}
+
+/// Decompress as much of a truncated or corrupted stream as possible. Returns the
+/// output up to the first unreadable code and whether the stream ended properly.
+pub fn decompress_partial(data: &[u8], max_len: usize) -> Result<(Vec<u16>, bool)> {
+    // A trailing odd byte is half a character and cannot be decoded
+    let even = &data[..data.len() - data.len() % 2];
+    let mut output = Vec::new();
+    let complete = decompress(even, max_len, &mut output)?;
+    Ok((output, complete && even.len() == data.len()))
+}
+
+// Ok(false) when malformed input stops decoding early, Err for an exceeded limit.
+// `output` holds everything decoded up to that point either way.
+fn decompress(data: &[u8], max_len: usize, output: &mut Vec<u16>) -> Result<bool> {
+    let Some(mut reader) = BitReader::new(data) else {
+        return Ok(true);
+    };
+
+    let first = match reader.read_bits(2) {
+        Some(U8_CODE) => reader.read_bits(8),
+        Some(U16_CODE) => reader.read_bits(16),
+        Some(CLOSE_CODE) => return Ok(true),
+        _ => None,
+    };
+    let Some(first) = first else {
+        return Ok(false);
+    };
+
+    // Codes 0-2 are the control codes above
+    let mut dictionary = vec![Entry::Char(0), Entry::Char(1), Entry::Char(2), Entry::Char(first as u16)];
+    output.push(first as u16);
+    let (mut w_start, mut w_len) = (0, 1);
+    let mut num_bits = 3;
+    let mut enlarge_in: u64 = 4;
+
+    loop {
+        let Some(mut code) = reader.read_bits(num_bits) else {
+            return Ok(false);
+        };
+
+        if code == U8_CODE || code == U16_CODE {
+            let Some(value) = reader.read_bits(if code == U8_CODE { 8 } else { 16 }) else {
+                return Ok(false);
+            };
+            dictionary.push(Entry::Char(value as u16));
+            code = (dictionary.len() - 1) as u32;
+            enlarge_in -= 1;
+        } else if code == CLOSE_CODE {
+            return Ok(true);
+        }
+
+        if enlarge_in == 0 {
+            if num_bits >= 31 {
+                return Ok(false);
+            }
+            enlarge_in = 1 << num_bits;
+            num_bits += 1;
+        }
+
+        let entry_start = output.len();
+        let entry_len = match dictionary.get(code as usize) {
+            Some(&Entry::Char(value)) => {
+                output.push(value);
+                1
+            }
+            Some(&Entry::Range { start, len }) => {
+                if output.len() + len > max_len {
+                    return Err(LimitExceeded::DecompressedSize(max_len).into());
+                }
+                output.extend_from_within(start..start + len);
+                len
+            }
+            // The entry being defined: the previous phrase plus its own first character
+            None if code as usize == dictionary.len() => {
+                if output.len() + w_len + 1 > max_len {
+                    return Err(LimitExceeded::DecompressedSize(max_len).into());
+                }
+                output.extend_from_within(w_start..w_start + w_len);
+                output.push(output[w_start]);
+                w_len + 1
+            }
+            None => return Ok(false),
+        };
+        if output.len() > max_len {
+            return Err(LimitExceeded::DecompressedSize(max_len).into());
+        }
+
+        // The previous phrase is immediately followed by this entry in the output
+        dictionary.push(Entry::Range { start: w_start, len: w_len + 1 });
+        enlarge_in -= 1;
+        (w_start, w_len) = (entry_start, entry_len);
+
+        if enlarge_in == 0 {
+            if num_bits >= 31 {
+                return Ok(false);
+            }
+            enlarge_in = 1 << num_bits;
+            num_bits += 1;
+        }
+    }
+}
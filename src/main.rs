@@ -1,15 +1,586 @@
-use anyhow::Result;
-use neo_replay_rs::{PchFile, renderer::Renderer};
+use anyhow::{bail, Result};
+use neo_replay_rs::{
+    PchFile,
+    contact_sheet::{self, Sampling, SheetOptions},
+    dump, edit,
+    export::{html::{self, PlayerOptions}, ora, psd, svg},
+    limits::Limits,
+    pacing::{PacingModel, Playback},
+    recover,
+    renderer::{FrameSet, FrameView, Renderer, StrokeAnimation, MAX_SCALE},
+    stats::ReplayStats,
+    upscale::Upscale,
+    validate::{self, Severity},
+    verify::{self, VerifyOptions},
+};
 use std::env;
+#[cfg(feature = "batch")]
+use neo_replay_rs::batch::{self, BatchOptions};
+
+const USAGE: &str = "Usage: neo-replay <pch_file> [options]
+       neo-replay export <pch_file> <output.ora|.psd|.svg> [--at N] [--scale N]
+       neo-replay sheet <pch_file> <output.png> [--frames N | --per-stroke] [--columns N]
+                        [--tile-width PX] [--no-captions]
+       neo-replay player <pch_file> <output_dir> [--frames N] [--speed X | --duration SECS]
+       neo-replay info <pch_file> [--json]
+       neo-replay validate <pch_file> [--json]
+       neo-replay verify <pch_file> <image> [--tolerance N] [--max-differing F] [--min-psnr DB]
+                         [--min-ssim S] [--heatmap output.png] [--json]
+       neo-replay dump <pch_file> [output.json|output.ndjson] [--ndjson]
+       neo-replay import <dump.json|dump.ndjson> <output.pch>
+       neo-replay trim <pch_file> <output.pch> <N>
+       neo-replay split <pch_file> <N> <first.pch> <second.pch>
+       neo-replay drop <pch_file> <output.pch> <FROM>..<TO>
+       neo-replay concat <output.pch> <pch_file>...
+       neo-replay recover <pch_file> <output.pch> [--json]
+       neo-replay batch <input_dir> <output_dir> [--jobs N] [--scale N] [--report report.json|.csv]
+                        [--max-time SECS] [--skip-existing]
+
+Options:
+  --stroke-points N      Emit an intermediate frame every N brush dabs
+  --stroke-distance PX   Emit an intermediate frame every PX pixels of stroke
+  --speed X              Pace frames at X times the estimated drawing speed
+  --duration SECS        Pace frames to fit a total of SECS seconds
+  --max-idle SECS        Clamp estimated pauses between actions
+  --cursor               Draw the pen position, brush size and color on frames
+  --hide-layer N         Leave layer N out of the composite (repeatable)
+  --layer-opacity N=A    Composite layer N at opacity A (0.0-1.0, repeatable)
+  --view VIEW            Composite image to write: composite, layer:N or dim:N[:A]
+  --scale N              Re-render strokes at N times the canvas size (1-8)
+  --upscale FILTER       Enlarge frames: nearest:F, integer:N (up to 8), scale2x, scale3x or scale4x";
+
+#[derive(Default)]
+struct Options {
+    pch_path: String,
+    stroke_animation: Option<StrokeAnimation>,
+    playback: Option<Playback>,
+    max_idle: Option<f64>,
+    show_cursor: bool,
+    hidden_layers: Vec<usize>,
+    layer_opacity: Vec<(usize, f32)>,
+    view: Option<FrameView>,
+    scale: u32,
+    upscale: Option<Upscale>,
+}
+
+fn parse_upscale(value: &str) -> Result<Upscale> {
+    let filter = match value.split_once(':') {
+        Some(("nearest", factor)) => Upscale::Nearest(factor.parse()?),
+        Some(("integer", factor)) => Upscale::Integer(factor.parse()?),
+        None if value == "scale2x" => Upscale::Scale2x,
+        None if value == "scale3x" => Upscale::Scale3x,
+        None if value == "scale4x" => Upscale::Scale4x,
+        _ => bail!("Invalid upscale filter: {}", value),
+    };
+    // Rejects factors outside the supported range
+    filter.output_size(1, 1)?;
+    Ok(filter)
+}
+
+fn parse_scale(value: &str) -> Result<u32> {
+    let scale = value.parse()?;
+    if !(1..=MAX_SCALE).contains(&scale) {
+        bail!("Scale must be between 1 and {}, got {}", MAX_SCALE, scale);
+    }
+    Ok(scale)
+}
+
+// `--speed X` or `--duration SECS`, both of which must be positive
+fn parse_playback(flag: &str, value: &str) -> Result<Playback> {
+    let number: f64 = value.parse()?;
+    if !number.is_finite() || number <= 0.0 {
+        bail!("{} must be greater than 0, got {}", flag, value);
+    }
+    Ok(if flag == "--speed" { Playback::Speed(number) } else { Playback::TargetDuration(number) })
+}
+
+fn parse_max_idle(value: &str) -> Result<f64> {
+    let seconds: f64 = value.parse()?;
+    if seconds.is_nan() || seconds < 0.0 {
+        bail!("--max-idle must not be negative, got {}", value);
+    }
+    Ok(seconds)
+}
+
+fn parse_view(value: &str) -> Result<FrameView> {
+    let parts: Vec<&str> = value.split(':').collect();
+    Ok(match parts.as_slice() {
+        ["composite"] => FrameView::Composite,
+        ["layer", layer] => FrameView::Layer(layer.parse()?),
+        ["dim", layer] => FrameView::Dimmed { layer: layer.parse()?, opacity: 0.3 },
+        ["dim", layer, opacity] => FrameView::Dimmed { layer: layer.parse()?, opacity: opacity.parse()? },
+        _ => bail!("Invalid view: {}", value),
+    })
+}
+
+fn parse_args(args: &[String]) -> Result<Options> {
+    let mut options = Options { scale: 1, ..Default::default() };
+    let mut pch_path = None;
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = args[i].as_str();
+        if !arg.starts_with("--") {
+            if pch_path.replace(arg.to_string()).is_some() {
+                bail!("Unexpected argument: {}", arg);
+            }
+            i += 1;
+            continue;
+        }
+
+        // Flags without a value
+        if arg == "--cursor" {
+            options.show_cursor = true;
+            i += 1;
+            continue;
+        }
+
+        let value = match args.get(i + 1) {
+            Some(value) if !value.starts_with("--") => value,
+            _ => bail!("Missing value for {}", arg),
+        };
+        match arg {
+            "--stroke-points" => options.stroke_animation = Some(StrokeAnimation::Points(value.parse()?)),
+            "--stroke-distance" => options.stroke_animation = Some(StrokeAnimation::Distance(value.parse()?)),
+            "--speed" | "--duration" => options.playback = Some(parse_playback(arg, value)?),
+            "--max-idle" => options.max_idle = Some(parse_max_idle(value)?),
+            "--hide-layer" => options.hidden_layers.push(value.parse()?),
+            "--layer-opacity" => {
+                let Some((layer, opacity)) = value.split_once('=') else {
+                    bail!("Expected N=OPACITY for --layer-opacity, got {}", value);
+                };
+                options.layer_opacity.push((layer.parse()?, opacity.parse()?));
+            }
+            "--view" => options.view = Some(parse_view(value)?),
+            "--scale" => options.scale = parse_scale(value)?,
+            "--upscale" => options.upscale = Some(parse_upscale(value)?),
+            _ => bail!("Unknown option: {}", arg),
+        }
+        i += 2;
+    }
+
+    let Some(pch_path) = pch_path else {
+        bail!("Missing PCH file");
+    };
+    options.pch_path = pch_path;
+    Ok(options)
+}
+
+fn exit_with_usage(error: anyhow::Error) -> ! {
+    eprintln!("{}\n\n{}", error, USAGE);
+    std::process::exit(1);
+}
 
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <pch_file>", args[0]);
+    match args.get(1).map(String::as_str) {
+        Some("export") => export(&args[2..]),
+        Some("sheet") => sheet(&args[2..]),
+        Some("player") => player(&args[2..]),
+        Some("info") => info(&args[2..]),
+        Some("validate") => validate_replay(&args[2..]),
+        Some("verify") => verify_replay(&args[2..]),
+        Some("dump") => dump_actions(&args[2..]),
+        Some("import") => import_actions(&args[2..]),
+        Some("trim" | "split" | "drop" | "concat") => edit_replay(&args[1], &args[2..]),
+        Some("recover") => recover_replay(&args[2..]),
+        #[cfg(feature = "batch")]
+        Some("batch") => batch(&args[2..]),
+        _ => render_frames(&args[1..]),
+    }
+}
+
+/// Export the canvas after the first N actions (all by default) as a layered document
+fn export(args: &[String]) -> Result<()> {
+    let parse = || -> Result<(&String, &String, Option<usize>, u32)> {
+        let [pch_path, output_path, flags @ ..] = args else {
+            bail!("Expected <pch_file> <output>");
+        };
+        let (mut at, mut scale) = (None, 1);
+        for pair in flags.chunks(2) {
+            match pair {
+                [flag, value] if flag == "--at" => at = Some(value.parse()?),
+                [flag, value] if flag == "--scale" => scale = parse_scale(value)?,
+                _ => bail!("Unexpected argument: {}", pair[0]),
+            }
+        }
+        Ok((pch_path, output_path, at, scale))
+    };
+    let (pch_path, output_path, at, scale) = parse().unwrap_or_else(|e| exit_with_usage(e));
+
+    let mut pch = PchFile::from_file(pch_path)?;
+    pch.fix_actions();
+
+    let mut renderer = Renderer::with_scale(pch.header.width as u32, pch.header.height as u32, scale)?;
+    let count = at.unwrap_or(pch.actions.len()).min(pch.actions.len());
+    renderer.render_to(&pch, count)?;
+
+    let extension = std::path::Path::new(output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("ora") => ora::save_ora(&renderer.canvas, output_path)?,
+        Some("psd") => psd::save_psd(&renderer.canvas, output_path)?,
+        Some("svg") => svg::save_svg(&pch, count, output_path)?,
+        _ => bail!("Unsupported export format: {}", output_path),
+    }
+
+    println!("Exported state after {}/{} actions to {}", count, pch.actions.len(), output_path);
+    Ok(())
+}
+
+/// Tile sampled replay states into a contact sheet with a JSON atlas next to it
+fn sheet(args: &[String]) -> Result<()> {
+    let parse = || -> Result<(&String, &String, SheetOptions)> {
+        let [pch_path, output_path, flags @ ..] = args else {
+            bail!("Expected <pch_file> <output.png>");
+        };
+        let mut options = SheetOptions::default();
+        let mut i = 0;
+        while i < flags.len() {
+            let value = flags.get(i + 1);
+            match (flags[i].as_str(), value) {
+                ("--per-stroke", _) => options.sampling = Sampling::PerStroke,
+                ("--no-captions", _) => options.captions = false,
+                ("--frames", Some(value)) => options.sampling = Sampling::Even(value.parse()?),
+                ("--columns", Some(value)) => options.columns = Some(value.parse()?),
+                ("--tile-width", Some(value)) => options.tile_width = value.parse()?,
+                (flag, _) => bail!("Unexpected argument: {}", flag),
+            }
+            i += if matches!(flags[i].as_str(), "--per-stroke" | "--no-captions") { 1 } else { 2 };
+        }
+        Ok((pch_path, output_path, options))
+    };
+    let (pch_path, output_path, options) = parse().unwrap_or_else(|e| exit_with_usage(e));
+
+    let mut pch = PchFile::from_file(pch_path)?;
+    pch.fix_actions();
+
+    let (image, atlas) = contact_sheet::contact_sheet(&pch, &options)?;
+    image.save(output_path)?;
+    let atlas_path = std::path::Path::new(output_path).with_extension("json");
+    std::fs::write(&atlas_path, serde_json::to_string_pretty(&atlas)?)?;
+
+    println!("Saved {} tiles to {} and atlas to {}", atlas.tiles.len(), output_path, atlas_path.display());
+    Ok(())
+}
+
+/// Write a static HTML5 player for the replay
+fn player(args: &[String]) -> Result<()> {
+    let parse = || -> Result<(&String, &String, PlayerOptions)> {
+        let [pch_path, output_dir, flags @ ..] = args else {
+            bail!("Expected <pch_file> <output_dir>");
+        };
+        let mut options = PlayerOptions::default();
+        for pair in flags.chunks(2) {
+            match pair {
+                [flag, value] if flag == "--frames" => options.frames = value.parse()?,
+                [flag, value] if flag == "--speed" || flag == "--duration" => options.playback = parse_playback(flag, value)?,
+                _ => bail!("Unexpected argument: {}", pair[0]),
+            }
+        }
+        Ok((pch_path, output_dir, options))
+    };
+    let (pch_path, output_dir, options) = parse().unwrap_or_else(|e| exit_with_usage(e));
+
+    let mut pch = PchFile::from_file(pch_path)?;
+    pch.fix_actions();
+
+    html::write_player_bundle(&pch, output_dir, &options)?;
+    println!("Player written to {}/index.html", output_dir);
+    Ok(())
+}
+
+/// Print statistics about the tools, colors and layers a replay uses
+fn info(args: &[String]) -> Result<()> {
+    let (pch_path, json) = match args {
+        [pch_path] => (pch_path, false),
+        [pch_path, flag] if flag == "--json" => (pch_path, true),
+        _ => exit_with_usage(anyhow::anyhow!("Expected <pch_file> [--json]")),
+    };
+
+    let mut pch = PchFile::from_file(pch_path)?;
+    pch.fix_actions();
+    let stats = ReplayStats::from_pch(&pch);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    let counts = |map: &std::collections::BTreeMap<String, usize>| {
+        map.iter().map(|(name, count)| format!("{} {}", name, count)).collect::<Vec<_>>().join(", ")
+    };
+    println!("Canvas:          {}x{}", stats.width, stats.height);
+    println!("Actions:         {} ({} malformed)", stats.actions, stats.malformed);
+    println!("Commands:        {}", counts(&stats.commands));
+    println!("Tools:           {}", counts(&stats.tools));
+    println!("Strokes:         {} with {} points, {:.0}px long", stats.strokes, stats.stroke_points, stats.stroke_length);
+    println!("Colors:          {} ({})", stats.colors.len(), stats.colors.join(" "));
+    let widths: Vec<String> = stats.widths.iter().map(|w| w.to_string()).collect();
+    println!("Widths:          {}", widths.join(", "));
+    let layers: Vec<String> = stats.layers.iter().map(|(layer, count)| format!("{}: {}", layer, count)).collect();
+    println!("Layers:          {}", layers.join(", "));
+    println!("Text:            {} strings, {} characters", stats.texts, stats.text_chars);
+    println!("Fills:           {} shapes, {} flood fills", stats.fills, stats.flood_fills);
+    println!("Estimated time:  {:.1}s", stats.duration);
+    Ok(())
+}
+
+fn is_ndjson(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("ndjson") || e.eq_ignore_ascii_case("jsonl"))
+}
+
+/// Write the action list as JSON or NDJSON, to stdout when no output file is given
+fn dump_actions(args: &[String]) -> Result<()> {
+    let (pch_path, output_path, ndjson) = match args {
+        [pch_path] => (pch_path, None, false),
+        [pch_path, flag] if flag == "--ndjson" => (pch_path, None, true),
+        [pch_path, output_path] => (pch_path, Some(output_path), is_ndjson(output_path)),
+        _ => exit_with_usage(anyhow::anyhow!("Expected <pch_file> [output] [--ndjson]")),
+    };
+
+    // Actions are dumped as stored, without fix_actions, so the file can be rebuilt exactly
+    let pch = PchFile::from_file(pch_path)?;
+    let text = if ndjson { dump::to_ndjson(&pch)? } else { dump::to_json(&pch)? };
+    match output_path {
+        Some(output_path) => {
+            std::fs::write(output_path, text)?;
+            println!("Dumped {} actions to {}", pch.actions.len(), output_path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// Rebuild a .pch file from a dump written by `dump`
+fn import_actions(args: &[String]) -> Result<()> {
+    let [dump_path, output_path] = args else {
+        exit_with_usage(anyhow::anyhow!("Expected <dump> <output.pch>"));
+    };
+
+    let text = std::fs::read_to_string(dump_path)?;
+    let pch = if is_ndjson(dump_path) { dump::from_ndjson(&text)? } else { dump::from_json(&text)? };
+    pch.to_file(output_path)?;
+    println!("Wrote {} actions to {}", pch.actions.len(), output_path);
+    Ok(())
+}
+
+/// Cut or join replays; action numbers are those after fix_actions, as in rendered frames
+fn edit_replay(command: &str, args: &[String]) -> Result<()> {
+    let parse_range = |value: &str| -> Result<std::ops::Range<usize>> {
+        let Some((start, end)) = value.split_once("..") else {
+            bail!("Expected FROM..TO, got {}", value);
+        };
+        Ok(start.parse()?..end.parse()?)
+    };
+
+    match (command, args) {
+        ("trim", [pch_path, output_path, end]) => {
+            let end = end.parse().unwrap_or_else(|e: std::num::ParseIntError| exit_with_usage(e.into()));
+            let pch = edit::trim(&PchFile::from_file(pch_path)?, end);
+            pch.to_file(output_path)?;
+            println!("Wrote {} actions to {}", pch.actions.len(), output_path);
+        }
+        ("split", [pch_path, index, first_path, second_path]) => {
+            let index = index.parse().unwrap_or_else(|e: std::num::ParseIntError| exit_with_usage(e.into()));
+            let (first, second) = edit::split_at(&PchFile::from_file(pch_path)?, index);
+            first.to_file(first_path)?;
+            second.to_file(second_path)?;
+            println!("Wrote {} actions to {} and {} to {}", first.actions.len(), first_path, second.actions.len(), second_path);
+        }
+        ("drop", [pch_path, output_path, range]) => {
+            let range = parse_range(range).unwrap_or_else(|e| exit_with_usage(e));
+            let pch = edit::drop_range(&PchFile::from_file(pch_path)?, range)?;
+            pch.to_file(output_path)?;
+            println!("Wrote {} actions to {}", pch.actions.len(), output_path);
+        }
+        ("concat", [output_path, pch_paths @ ..]) if !pch_paths.is_empty() => {
+            let parts = pch_paths.iter().map(PchFile::from_file).collect::<Result<Vec<_>>>()?;
+            let pch = edit::concat(&parts)?;
+            pch.to_file(output_path)?;
+            println!("Joined {} replays into {} actions in {}", parts.len(), pch.actions.len(), output_path);
+        }
+        _ => exit_with_usage(anyhow::anyhow!("Invalid arguments for {}", command)),
+    }
+    Ok(())
+}
+
+/// Check a file without rendering it. Exits with status 1 if it has errors.
+fn validate_replay(args: &[String]) -> Result<()> {
+    let (pch_path, json) = match args {
+        [pch_path] => (pch_path, false),
+        [pch_path, flag] if flag == "--json" => (pch_path, true),
+        _ => exit_with_usage(anyhow::anyhow!("Expected <pch_file> [--json]")),
+    };
+
+    let data = std::fs::read(pch_path)?;
+    let report = validate::validate_bytes(&data, &Limits::default());
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for issue in &report.issues {
+            let severity = if issue.severity == Severity::Error { "error" } else { "warning" };
+            match issue.action {
+                Some(action) => println!("{}: action {}: {} [{}]", severity, action, issue.message, issue.code),
+                None => println!("{}: {} [{}]", severity, issue.message, issue.code),
+            }
+        }
+        if report.issues.len() < report.errors + report.warnings {
+            println!("... {} more", report.errors + report.warnings - report.issues.len());
+        }
+        println!("{}: {} errors, {} warnings", pch_path, report.errors, report.warnings);
+    }
+
+    if !report.valid {
         std::process::exit(1);
     }
+    Ok(())
+}
+
+/// Compare the final state with the posted image. Exits with status 1 if they differ.
+fn verify_replay(args: &[String]) -> Result<()> {
+    let parse = || -> Result<(&String, &String, VerifyOptions, Option<&String>, bool)> {
+        let [pch_path, image_path, flags @ ..] = args else {
+            bail!("Expected <pch_file> <image>");
+        };
+        let mut options = VerifyOptions::default();
+        let (mut heatmap_path, mut json) = (None, false);
+        let mut i = 0;
+        while i < flags.len() {
+            match (flags[i].as_str(), flags.get(i + 1)) {
+                ("--json", _) => json = true,
+                ("--tolerance", Some(value)) => options.tolerance = value.parse()?,
+                ("--max-differing", Some(value)) => options.max_differing = value.parse()?,
+                ("--min-psnr", Some(value)) => options.min_psnr = Some(value.parse()?),
+                ("--min-ssim", Some(value)) => options.min_ssim = Some(value.parse()?),
+                ("--heatmap", Some(value)) => heatmap_path = Some(value),
+                (flag, _) => bail!("Unexpected argument: {}", flag),
+            }
+            i += if flags[i] == "--json" { 1 } else { 2 };
+        }
+        Ok((pch_path, image_path, options, heatmap_path, json))
+    };
+    let (pch_path, image_path, options, heatmap_path, json) = parse().unwrap_or_else(|e| exit_with_usage(e));
+
+    let pch = PchFile::from_file(pch_path)?;
+    let rendered = verify::render_final(&pch, &Limits::default())?;
+    let posted = verify::flatten(&image::open(image_path)?);
+    let comparison = verify::compare(&rendered, &posted, &options)?;
+    if let Some(heatmap_path) = heatmap_path {
+        verify::diff_heatmap(&rendered, &posted, options.tolerance)?.save(heatmap_path)?;
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&comparison)?);
+    } else {
+        println!(
+            "{}: {} ({} of {} pixels differ, max difference {}, PSNR {:.2} dB, SSIM {:.4})",
+            pch_path,
+            if comparison.exact { "exact match" } else if comparison.matches { "matches" } else { "does not match" },
+            comparison.differing_pixels,
+            comparison.width * comparison.height,
+            comparison.max_difference,
+            comparison.psnr,
+            comparison.ssim
+        );
+    }
 
-    let pch_path = &args[1];
+    if !comparison.matches {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Salvage the complete actions of a truncated or corrupted file
+fn recover_replay(args: &[String]) -> Result<()> {
+    let (pch_path, output_path, json) = match args {
+        [pch_path, output_path] => (pch_path, output_path, false),
+        [pch_path, output_path, flag] if flag == "--json" => (pch_path, output_path, true),
+        _ => exit_with_usage(anyhow::anyhow!("Expected <pch_file> <output.pch> [--json]")),
+    };
+
+    let data = std::fs::read(pch_path)?;
+    let (pch, report) = recover::recover(&data, &Limits::unlimited())?;
+    pch.to_file(output_path)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if report.complete {
+        println!("{} is intact; wrote all {} actions to {}", pch_path, report.actions, output_path);
+    } else {
+        println!("{}", report.problem.as_deref().unwrap_or_default());
+        println!(
+            "Recovered {} actions to {}; {} of {} decompressed characters were unusable",
+            report.actions, output_path, report.unused_chars, report.decompressed_chars
+        );
+    }
+    Ok(())
+}
+
+/// Render the final image of every replay in a directory tree and write a report
+#[cfg(feature = "batch")]
+fn batch(args: &[String]) -> Result<()> {
+    let parse = || -> Result<(&String, &String, BatchOptions, Option<&String>)> {
+        let [input_dir, output_dir, flags @ ..] = args else {
+            bail!("Expected <input_dir> <output_dir>");
+        };
+        let mut options = BatchOptions::default();
+        let mut report_path = None;
+        let mut i = 0;
+        while i < flags.len() {
+            let value = flags.get(i + 1);
+            match (flags[i].as_str(), value) {
+                ("--skip-existing", _) => options.skip_existing = true,
+                ("--jobs", Some(value)) => options.jobs = Some(value.parse()?),
+                ("--scale", Some(value)) => options.scale = parse_scale(value)?,
+                ("--report", Some(value)) => report_path = Some(value),
+                ("--max-time", Some(value)) => {
+                    options.limits.max_render_time = Some(std::time::Duration::from_secs_f64(value.parse()?))
+                }
+                (flag, _) => bail!("Unexpected argument: {}", flag),
+            }
+            i += if flags[i] == "--skip-existing" { 1 } else { 2 };
+        }
+        Ok((input_dir, output_dir, options, report_path))
+    };
+    let (input_dir, output_dir, options, report_path) = parse().unwrap_or_else(|e| exit_with_usage(e));
+
+    let report = batch::render_directory(input_dir.as_ref(), output_dir.as_ref(), &options)?;
+    for file in report.files.iter().filter(|file| file.error.is_some()) {
+        eprintln!("{}: {}", file.input, file.error.as_deref().unwrap_or_default());
+    }
+
+    let report_path = match report_path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => std::path::Path::new(output_dir).join("report.json"),
+    };
+    std::fs::create_dir_all(output_dir)?;
+    if report_path.extension().is_some_and(|e| e.eq_ignore_ascii_case("csv")) {
+        report.save_csv(&report_path)?;
+    } else {
+        report.save_json(&report_path)?;
+    }
+
+    println!(
+        "Rendered {}, skipped {}, failed {} in {:.1}s; report saved to {}",
+        report.rendered,
+        report.skipped,
+        report.failed,
+        report.elapsed_ms / 1000.0,
+        report_path.display()
+    );
+    Ok(())
+}
+
+fn render_frames(args: &[String]) -> Result<()> {
+    let options = parse_args(args).unwrap_or_else(|e| exit_with_usage(e));
+
+    let pch_path = &options.pch_path;
     println!("Loading PCH file: {}", pch_path);
 
     // Load and parse PCH file
@@ -22,37 +593,76 @@ fn main() -> Result<()> {
     println!("Actions after fixing: {}", pch.actions.len());
 
     // Create renderer
-    let mut renderer = Renderer::new(pch.header.width as u32, pch.header.height as u32);
+    let mut renderer = Renderer::with_scale(pch.header.width as u32, pch.header.height as u32, options.scale)?;
+    renderer.stroke_animation = options.stroke_animation;
+    renderer.show_cursor = options.show_cursor;
+    let layer_count = renderer.canvas.layer_count();
+    let view_layer = match options.view {
+        Some(FrameView::Layer(layer) | FrameView::Dimmed { layer, .. }) => Some(layer),
+        _ => None,
+    };
+    let layers = options.hidden_layers.iter().chain(options.layer_opacity.iter().map(|(layer, _)| layer));
+    if let Some(layer) = layers.chain(&view_layer).find(|&&layer| layer >= layer_count) {
+        bail!("Layer {} does not exist; the canvas has {} layers", layer, layer_count);
+    }
+    for &layer in &options.hidden_layers {
+        renderer.canvas.set_visible(layer, false);
+    }
+    for &(layer, opacity) in &options.layer_opacity {
+        renderer.canvas.set_opacity(layer, opacity);
+    }
+    if let Some(view) = options.view {
+        renderer.view = view;
+    }
+    renderer.set_upscale(options.upscale)?;
 
-    // Render frame by frame
+    // Render frame by frame, with estimated display times when pacing was requested
     println!("Rendering frames...");
-    let frames = renderer.render_frame_by_frame(&pch)?;
+    let (frames, durations): (Vec<FrameSet>, Option<Vec<f64>>) =
+        if options.playback.is_some() || options.max_idle.is_some() {
+            let timeline = PacingModel::default()
+                .timeline(&pch, options.max_idle)
+                .apply(options.playback.unwrap_or(Playback::Speed(1.0)));
+            println!("Estimated replay length: {:.1}s", timeline.total());
+            let timed = renderer.render_paced(&pch, &timeline)?;
+            let durations = timed.iter().map(|t| t.duration).collect();
+            (timed.into_iter().map(|t| t.frame).collect(), Some(durations))
+        } else {
+            (renderer.render_frame_by_frame(&pch)?, None)
+        };
     println!("Generated {} frames", frames.len());
+    for warning in &renderer.warnings {
+        eprintln!("Warning: {}", warning);
+    }
 
     // Save all frames with separate layers
     let output_dir = "output_frames";
     std::fs::create_dir_all(output_dir)?;
 
     for (i, frame_set) in frames.iter().enumerate() {
-        // Save layer 0
-        let layer0_filename = format!("{}/frame_{:06}_layer_0.png", output_dir, i);
-        frame_set.layer0.save(&layer0_filename)?;
-        
-        // Save layer 1
-        let layer1_filename = format!("{}/frame_{:06}_layer_1.png", output_dir, i);
-        frame_set.layer1.save(&layer1_filename)?;
-        
+        // Save each layer
+        for (layer, image) in frame_set.layers.iter().enumerate() {
+            let layer_filename = format!("{}/frame_{:06}_layer_{}.png", output_dir, i, layer);
+            image.save(&layer_filename)?;
+        }
+
         // Save composite
         let composite_filename = format!("{}/frame_{:06}_composite.png", output_dir, i);
         frame_set.composite.save(&composite_filename)?;
 
         if i % 100 == 0 || i == frames.len() - 1 {
-            println!("Saved frame {}/{} (layer0, layer1, composite)", i + 1, frames.len());
+            println!("Saved frame {}/{} (layers, composite)", i + 1, frames.len());
         }
     }
 
+    if let Some(durations) = durations {
+        let timing_filename = format!("{}/timing.json", output_dir);
+        std::fs::write(&timing_filename, serde_json::to_string_pretty(&durations)?)?;
+        println!("Frame durations (seconds) saved to {}", timing_filename);
+    }
+
     println!("All frames saved to {}/", output_dir);
-    println!("Each frame includes: frame_XXXXXX_layer_0.png, frame_XXXXXX_layer_1.png, frame_XXXXXX_composite.png");
+    println!("Each frame includes: frame_XXXXXX_layer_N.png for every layer and frame_XXXXXX_composite.png");
 
     Ok(())
-}
\ No newline at end of file
+}
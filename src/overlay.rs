@@ -0,0 +1,92 @@
+use crate::{Color, LineType};
+use image::{Rgb, RgbImage};
+
+const SWATCH_SIZE: u32 = 12;
+const SWATCH_MARGIN: u32 = 4;
+const CROSSHAIR_SIZE: i32 = 3;
+
+/// Pen position and tool state drawn on top of emitted frames.
+///
+/// The overlay is only ever applied to output images, never to `Canvas` layers.
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    pub x: i32,
+    pub y: i32,
+    pub width: f64,
+    pub color: Color,
+    pub line_type: Option<LineType>, // None for tools without a brush tip (fills, text, clipboard)
+}
+
+impl Cursor {
+    pub fn draw(&self, image: &mut RgbImage) {
+        if let Some(line_type) = self.line_type {
+            let radius = (self.width / 2.0).max(1.0) + 1.0;
+            self.draw_circle(image, radius);
+            if line_type == LineType::Eraser {
+                self.draw_circle(image, radius + 2.0);
+            }
+        }
+
+        // Crosshair at the exact pen position
+        for d in -CROSSHAIR_SIZE..=CROSSHAIR_SIZE {
+            if d != 0 {
+                invert_pixel(image, self.x.saturating_add(d), self.y);
+                invert_pixel(image, self.x, self.y.saturating_add(d));
+            }
+        }
+
+        self.draw_swatch(image);
+    }
+
+    fn draw_circle(&self, image: &mut RgbImage, radius: f64) {
+        // One pixel per step along the circumference, deduplicated so inversion is not undone
+        let steps = ((radius * std::f64::consts::TAU).ceil() as usize).max(8);
+        let mut drawn = Vec::with_capacity(steps);
+        for step in 0..steps {
+            let angle = step as f64 / steps as f64 * std::f64::consts::TAU;
+            let px = self.x.saturating_add((radius * angle.cos()).round() as i32);
+            let py = self.y.saturating_add((radius * angle.sin()).round() as i32);
+            if !drawn.contains(&(px, py)) {
+                drawn.push((px, py));
+                invert_pixel(image, px, py);
+            }
+        }
+    }
+
+    fn draw_swatch(&self, image: &mut RgbImage) {
+        // Current color in the bottom-left corner, blended onto white like the canvas background
+        let (width, height) = image.dimensions();
+        if width < SWATCH_SIZE + SWATCH_MARGIN * 2 || height < SWATCH_SIZE + SWATCH_MARGIN * 2 {
+            return;
+        }
+
+        let left = SWATCH_MARGIN;
+        let top = height - SWATCH_MARGIN - SWATCH_SIZE;
+        let alpha = self.color.a as f32 / 255.0;
+        let blend = |c: u8| (c as f32 * alpha + 255.0 * (1.0 - alpha)) as u8;
+        let fill = Rgb([blend(self.color.r), blend(self.color.g), blend(self.color.b)]);
+        let erasing = self.line_type == Some(LineType::Eraser);
+
+        for y in 0..SWATCH_SIZE {
+            for x in 0..SWATCH_SIZE {
+                let border = x == 0 || y == 0 || x == SWATCH_SIZE - 1 || y == SWATCH_SIZE - 1;
+                let pixel = if border {
+                    Rgb([0, 0, 0])
+                } else if erasing {
+                    // Diagonal cross on white marks the eraser
+                    if x == y || x + y == SWATCH_SIZE - 1 { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) }
+                } else {
+                    fill
+                };
+                image.put_pixel(left + x, top + y, pixel);
+            }
+        }
+    }
+}
+
+fn invert_pixel(image: &mut RgbImage, x: i32, y: i32) {
+    if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+        let pixel = image.get_pixel_mut(x as u32, y as u32);
+        *pixel = Rgb([255 - pixel[0], 255 - pixel[1], 255 - pixel[2]]);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::action::Action;
+use crate::{LineType, PchFile};
+
+/// Estimates how long each action took to draw.
+///
+/// PCH files carry no timestamps, so durations are derived from point count,
+/// path length and tool, roughly matching how the NEO player paces a replay
+/// at its normal speed setting. All times are in seconds.
+#[derive(Debug, Clone)]
+pub struct PacingModel {
+    pub stroke_base: f64,       // Pen-down overhead for every stroke
+    pub per_point: f64,         // Per recorded point in a stroke
+    pub per_pixel: f64,         // Per pixel of path length
+    pub shape_base: f64,        // Rectangle/ellipse fills
+    pub flood_fill: f64,
+    pub text_base: f64,
+    pub text_per_char: f64,
+    pub clipboard: f64,         // copy, paste and merge
+    pub erase: f64,             // clearCanvas and eraseAll
+    pub pause: f64,             // Pen-up gap before an action that keeps the same tool
+    pub tool_change_pause: f64, // Gap before an action that changes tool, layer, color or width
+}
+
+/// How the estimated timeline is mapped onto output time.
+#[derive(Debug, Clone, Copy)]
+pub enum Playback {
+    /// Play back at a multiple of the estimated drawing speed (1.0 = real time)
+    Speed(f64),
+    /// Stretch or compress the whole replay to this many seconds
+    TargetDuration(f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct TimelineEntry {
+    pub action_index: usize,
+    pub start: f64,    // Seconds from the beginning of the replay
+    pub idle: f64,     // Gap before the action starts drawing
+    pub duration: f64, // Time spent drawing the action itself
+}
+
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    pub entries: Vec<TimelineEntry>,
+}
+
+impl Default for PacingModel {
+    fn default() -> Self {
+        Self {
+            stroke_base: 0.1,
+            per_point: 0.008,
+            per_pixel: 0.002,
+            shape_base: 0.6,
+            flood_fill: 0.4,
+            text_base: 0.8,
+            text_per_char: 0.15,
+            clipboard: 0.8,
+            erase: 0.3,
+            pause: 0.25,
+            tool_change_pause: 1.2,
+        }
+    }
+}
+
+impl PacingModel {
+    /// Estimated drawing time of a single action, excluding the pause before it
+    pub fn action_duration(&self, action: &Action) -> f64 {
+        match action {
+            Action::FreeHand(stroke) | Action::Line(stroke) | Action::Bezier(stroke) => {
+                let tool_factor = match stroke.line_type {
+                    LineType::Brush | LineType::Tone | LineType::Blur => 1.25,
+                    LineType::Dodge | LineType::Burn => 1.5,
+                    _ => 1.0,
+                };
+                (self.stroke_base
+                    + stroke.points.len() as f64 * self.per_point
+                    + stroke.path_length() * self.per_pixel)
+                    * tool_factor
+            }
+            Action::Fill(_) => self.shape_base,
+            Action::FloodFill { .. } => self.flood_fill,
+            Action::Text(text) => self.text_base + text.text.chars().count() as f64 * self.text_per_char,
+            Action::Copy { .. } | Action::Paste { .. } | Action::Merge { .. } => self.clipboard,
+            Action::ClearCanvas | Action::EraseAll { .. } => self.erase,
+            Action::Restore | Action::Unknown(_) => 0.0,
+        }
+    }
+
+    /// Estimated pen-up time between `previous` and `action`
+    pub fn pause_before(&self, previous: Option<&Action>, action: &Action) -> f64 {
+        let Some(previous) = previous else {
+            return 0.0;
+        };
+
+        let same_tool = match (previous.stroke(), action.stroke()) {
+            (Some(a), Some(b)) => {
+                a.layer == b.layer
+                    && a.line_type == b.line_type
+                    && a.width == b.width
+                    && a.color == b.color
+            }
+            _ => previous.command() == action.command(),
+        };
+
+        if same_tool {
+            self.pause
+        } else {
+            self.tool_change_pause
+        }
+    }
+
+    /// Build the estimated timeline for every action in `pch`.
+    ///
+    /// `max_idle` compresses long pauses: any gap longer than it is clamped. Negative
+    /// values are treated as 0.
+    pub fn timeline(&self, pch: &PchFile, max_idle: Option<f64>) -> Timeline {
+        let mut entries = Vec::with_capacity(pch.actions.len());
+        let mut previous: Option<Action> = None;
+        let mut time = 0.0;
+
+        for (action_index, values) in pch.actions.iter().enumerate() {
+            let (idle, duration) = match Action::parse(values) {
+                Some(action) => {
+                    let mut idle = self.pause_before(previous.as_ref(), &action);
+                    if let Some(max_idle) = max_idle {
+                        idle = idle.min(max_idle).max(0.0);
+                    }
+                    let duration = self.action_duration(&action);
+                    previous = Some(action);
+                    (idle, duration)
+                }
+                None => (0.0, 0.0),
+            };
+
+            entries.push(TimelineEntry {
+                action_index,
+                start: time + idle,
+                idle,
+                duration,
+            });
+            time += idle + duration;
+        }
+
+        Timeline { entries }
+    }
+}
+
+impl Timeline {
+    /// Total estimated length in seconds
+    pub fn total(&self) -> f64 {
+        self.entries.last().map_or(0.0, |entry| entry.start + entry.duration)
+    }
+
+    /// Rescale all times for the given playback mode
+    pub fn apply(&self, playback: Playback) -> Timeline {
+        let factor = match playback {
+            Playback::Speed(speed) if speed > 0.0 => 1.0 / speed,
+            Playback::TargetDuration(seconds) if self.total() > 0.0 => seconds.max(0.0) / self.total(),
+            _ => 1.0,
+        };
+
+        Timeline {
+            entries: self
+                .entries
+                .iter()
+                .map(|entry| TimelineEntry {
+                    action_index: entry.action_index,
+                    start: entry.start * factor,
+                    idle: entry.idle * factor,
+                    duration: entry.duration * factor,
+                })
+                .collect(),
+        }
+    }
+}
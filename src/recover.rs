@@ -0,0 +1,118 @@
+//! Salvaging replays from truncated or corrupted files.
+//!
+//! A cut-off upload usually still holds most of the drawing: the compressed stream
+//! decodes fine up to the cut, and the JSON action list is only missing its tail.
+//! `recover` keeps every complete action before the first damage and reports what
+//! had to be dropped.
+
+use crate::limits::Limits;
+use crate::{check_action_limits, lzstring, ActionValue, PchFile};
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Deserializer;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveryReport {
+    pub complete: bool, // Nothing was lost; the file parses normally
+    pub stream_complete: bool, // The compressed data ended with its end marker
+    pub list_complete: bool, // The JSON action list was closed
+    pub actions: usize, // Complete actions salvaged
+    pub decompressed_chars: usize, // Characters recovered from the compressed data
+    pub unused_chars: usize, // Decompressed characters after the last complete action
+    pub problem: Option<String>, // First damage found, when not complete
+}
+
+/// Salvage the header and leading complete actions of a damaged `.pch` file.
+/// Fails only when the header is unusable or a bound in `limits` is exceeded.
+pub fn recover(data: &[u8], limits: &Limits) -> Result<(PchFile, RecoveryReport)> {
+    let header = PchFile::parse_header(data, limits)?;
+    let (decompressed, stream_complete) = lzstring::decompress_partial(&data[12..], limits.max_decompressed_size)?;
+    // Decoded like `PchFile::from_bytes`, which drops surrogates
+    let text: String = decompressed.into_iter().filter_map(|c| char::from_u32(c as u32)).collect();
+
+    let salvage = salvage_actions(&text);
+    check_action_limits(&salvage.actions, limits)?;
+
+    let problem = match (stream_complete, salvage.problem) {
+        (_, Some(problem)) => Some(problem),
+        (false, None) => Some("Compressed data is cut off or damaged after the action list".to_string()),
+        (true, None) => None,
+    };
+    let report = RecoveryReport {
+        complete: problem.is_none(),
+        stream_complete,
+        list_complete: salvage.list_complete,
+        actions: salvage.actions.len(),
+        decompressed_chars: text.chars().count(),
+        unused_chars: text[salvage.used..].chars().count(),
+        problem,
+    };
+    Ok((PchFile { header, actions: salvage.actions }, report))
+}
+
+struct Salvage {
+    actions: Vec<Vec<ActionValue>>,
+    list_complete: bool,
+    used: usize, // Bytes of text up to the end of the last complete action
+    problem: Option<String>,
+}
+
+// Read actions one at a time from a JSON list that may stop or turn to garbage anywhere
+fn salvage_actions(text: &str) -> Salvage {
+    let mut salvage = Salvage { actions: Vec::new(), list_complete: false, used: 0, problem: None };
+    let skip_whitespace = |pos: usize| pos + (text[pos..].len() - text[pos..].trim_start().len());
+
+    let mut pos = skip_whitespace(0);
+    if !text[pos..].starts_with('[') {
+        salvage.problem = Some(if text[pos..].is_empty() {
+            "No action data".to_string()
+        } else {
+            "Action data does not start with a list".to_string()
+        });
+        return salvage;
+    }
+    pos = skip_whitespace(pos + 1);
+    if text[pos..].starts_with(']') {
+        salvage.list_complete = true;
+        salvage.used = pos + 1;
+        return salvage;
+    }
+
+    loop {
+        let index = salvage.actions.len();
+        let mut stream = Deserializer::from_str(&text[pos..]).into_iter::<Vec<ActionValue>>();
+        match stream.next() {
+            Some(Ok(action)) => {
+                pos += stream.byte_offset();
+                salvage.actions.push(action);
+                salvage.used = pos;
+            }
+            Some(Err(e)) if !e.is_eof() => {
+                salvage.problem = Some(format!("Action {} is invalid: {}", index, e));
+                return salvage;
+            }
+            _ => {
+                salvage.problem = Some(format!("Action {} is cut off", index));
+                return salvage;
+            }
+        }
+
+        pos = skip_whitespace(pos);
+        match text[pos..].chars().next() {
+            Some(',') => pos = skip_whitespace(pos + 1),
+            Some(']') => {
+                salvage.list_complete = true;
+                salvage.used = pos + 1;
+                return salvage;
+            }
+            Some(c) => {
+                salvage.problem = Some(format!("Unexpected {:?} after action {}", c, index));
+                return salvage;
+            }
+            None => {
+                salvage.problem = Some(format!("Action list is cut off after action {}", index));
+                return salvage;
+            }
+        }
+    }
+}
@@ -1,17 +1,48 @@
-use crate::{ActionValue, Color, DrawingState, LineType, MaskType, PchFile, AlphaType, FillType};
+use crate::{ActionValue, Color, DrawingState, LineType, MaskType, PchFile, AlphaType};
+use crate::overlay::Cursor;
+use crate::limits::LimitExceeded;
+use crate::pacing::Timeline;
+use crate::upscale::{upscale, Upscale};
 use anyhow::{bail, Result};
 use image::{ImageBuffer, Rgb, RgbImage, Rgba, RgbaImage};
 use ab_glyph::{FontRef, PxScale, point, Font};
+use std::time::{Duration, Instant};
+#[cfg(feature = "system-fonts")]
 use font_kit::family_name::FamilyName;
+#[cfg(feature = "system-fonts")]
 use font_kit::properties::Properties;
+#[cfg(feature = "system-fonts")]
 use font_kit::source::SystemSource;
+#[cfg(feature = "system-fonts")]
+use std::sync::OnceLock;
+
+// Text beyond this size (before upscaling) cannot fit on any canvas NEO allows
+const MAX_FONT_SIZE: f64 = 512.0;
+// Distinct warnings kept per renderer, so broken files cannot grow the list without bound
+const MAX_WARNINGS: usize = 100;
+// Dabs or flood fill steps between reads of the clock when a time limit is set
+const TIME_CHECK_INTERVAL: u32 = 256;
+
+/// Layers of a NEO canvas, used by `Canvas::new` and `Renderer::new`
+pub const NEO_LAYERS: usize = 2;
 
 pub struct Canvas {
-    pub layers: [RgbaImage; 2], // Two layers with alpha support
+    pub layers: Vec<RgbaImage>, // Layers with alpha support, two for NEO replays
     pub width: u32,
     pub height: u32,
     pub current_layer: usize,
-    pub visible: [bool; 2],
+    pub visible: Vec<bool>,
+    pub opacity: Vec<f32>, // Per-layer opacity (0.0-1.0) applied when compositing
+    pub order: Vec<usize>, // Compositing order, bottom to top, as indices into `layers`
+}
+
+/// How often intermediate frames are emitted while a stroke is being drawn.
+#[derive(Debug, Clone, Copy)]
+pub enum StrokeAnimation {
+    /// Emit a frame every N brush dabs along the rasterized path
+    Points(usize),
+    /// Emit a frame every N pixels of path length
+    Distance(f64),
 }
 
 pub struct Renderer {
@@ -21,22 +52,54 @@ pub struct Renderer {
     pub tone_data: Vec<Vec<u8>>, // 4x4 dithering patterns for tone brush (16 levels)
     pub arial_font: Option<FontRef<'static>>, // Arial font for text rendering
     pub clipboard: Option<Vec<u32>>, // Temporary storage for copy/paste operations (RGBA data)
+    pub stroke_animation: Option<StrokeAnimation>, // Emit sub-action frames inside strokes when set
+    stroke_progress: f64, // Dabs or pixels drawn since the last intermediate frame
+    pending_frames: Vec<FrameSet>, // Intermediate frames captured during the current action
+    pub show_cursor: bool, // Draw the pen position, brush size and color on emitted frames
+    cursor: Option<Cursor>, // Last pen position, only used for the frame overlay
+    pub view: FrameView, // What the composite image of each emitted frame shows
+    pub scale: u32, // Integer factor strokes are re-rasterized at; the canvas is this many times larger
+    upscale: Option<Upscale>, // Filter applied to emitted frames, set with `set_upscale`
+    time_limit: Option<(Instant, Duration)>, // Deadline and the budget it was set from
+    pub warnings: Vec<String>, // Problems with the replay that were skipped over instead of failing
 }
 
 impl Canvas {
     pub fn new(width: u32, height: u32) -> Self {
+        Self::with_layers(width, height, NEO_LAYERS)
+    }
+
+    pub fn with_layers(width: u32, height: u32, count: usize) -> Self {
         Self {
-            layers: [
-                ImageBuffer::new(width, height),
-                ImageBuffer::new(width, height),
-            ],
+            layers: (0..count).map(|_| ImageBuffer::new(width, height)).collect(),
             width,
             height,
             current_layer: 0,
-            visible: [true, true],
+            visible: vec![true; count],
+            opacity: vec![1.0; count],
+            order: (0..count).collect(),
         }
     }
 
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Layer that `merge` combines into `layer`: the one stacked directly above it,
+    /// or directly below when `layer` is on top
+    pub fn merge_source(&self, layer: usize) -> Option<usize> {
+        let position = self.order.iter().position(|&l| l == layer)?;
+        self.order
+            .get(position + 1)
+            .or_else(|| position.checked_sub(1).and_then(|p| self.order.get(p)))
+            .copied()
+    }
+
+    /// Position of `layer` in the compositing order, bottom is 0
+    pub fn stack_position(&self, layer: usize) -> Option<usize> {
+        self.order.iter().position(|&l| l == layer)
+    }
+
     pub fn clear(&mut self) {
         for layer in &mut self.layers {
             for pixel in layer.pixels_mut() {
@@ -46,14 +109,61 @@ impl Canvas {
     }
 
     pub fn clear_layer(&mut self, layer: usize) {
-        if layer < 2 {
+        if layer < self.layers.len() {
             for pixel in self.layers[layer].pixels_mut() {
                 *pixel = Rgba([0, 0, 0, 0]); // Fully transparent
             }
         }
     }
 
+    pub fn set_visible(&mut self, layer: usize, visible: bool) {
+        if let Some(v) = self.visible.get_mut(layer) {
+            *v = visible;
+        }
+    }
+
+    pub fn is_visible(&self, layer: usize) -> bool {
+        self.visible.get(layer).copied().unwrap_or(false)
+    }
+
+    pub fn set_opacity(&mut self, layer: usize, opacity: f32) {
+        if let Some(o) = self.opacity.get_mut(layer) {
+            *o = opacity.clamp(0.0, 1.0);
+        }
+    }
+
+    pub fn get_opacity(&self, layer: usize) -> f32 {
+        self.opacity.get(layer).copied().unwrap_or(0.0)
+    }
+
+    /// Layer opacity as used by `composite`, zero when hidden
+    fn effective_opacity(&self, layer: usize) -> f32 {
+        if self.is_visible(layer) {
+            self.get_opacity(layer).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
     pub fn composite(&self) -> RgbImage {
+        self.composite_with(|layer| self.effective_opacity(layer))
+    }
+
+    /// Render the canvas as seen through `view`
+    pub fn render_view(&self, view: FrameView) -> RgbImage {
+        match view {
+            FrameView::Composite => self.composite(),
+            FrameView::Layer(only) => self.composite_with(|layer| {
+                if layer == only { self.get_opacity(layer).clamp(0.0, 1.0) } else { 0.0 }
+            }),
+            FrameView::Dimmed { layer: dimmed, opacity } => self.composite_with(|layer| {
+                let base = self.effective_opacity(layer);
+                if layer == dimmed { base * opacity.clamp(0.0, 1.0) } else { base }
+            }),
+        }
+    }
+
+    fn composite_with(&self, layer_opacity: impl Fn(usize) -> f32) -> RgbImage {
         let mut result = ImageBuffer::new(self.width, self.height);
         
         // Start with white background for final composite
@@ -61,15 +171,19 @@ impl Canvas {
             *pixel = Rgb([255, 255, 255]);
         }
 
-        // Composite visible layers in order (Layer 0, then Layer 1)
-        for (layer_idx, layer) in self.layers.iter().enumerate() {
-            if self.visible[layer_idx] {
+        // Composite visible layers bottom to top
+        for &layer_idx in &self.order {
+            let Some(layer) = self.layers.get(layer_idx) else {
+                continue;
+            };
+            let opacity = layer_opacity(layer_idx);
+            if opacity > 0.0 {
                 for (x, y, pixel) in layer.enumerate_pixels() {
                     if pixel.0[3] > 0 { // If foreground has alpha
                         let bg = result.get_pixel(x, y);
                         let fg = pixel;
                         
-                        let alpha = fg.0[3] as f32 / 255.0;
+                        let alpha = fg.0[3] as f32 / 255.0 * opacity;
                         let inv_alpha = 1.0 - alpha;
                         
                         let r = (fg.0[0] as f32 * alpha + bg.0[0] as f32 * inv_alpha) as u8;
@@ -86,15 +200,11 @@ impl Canvas {
     }
 
     pub fn get_layer(&self, layer: usize) -> Option<&RgbaImage> {
-        if layer < 2 {
-            Some(&self.layers[layer])
-        } else {
-            None
-        }
+        self.layers.get(layer)
     }
     
     pub fn get_layer_as_rgb(&self, layer: usize) -> Option<RgbImage> {
-        if layer < 2 {
+        if layer < self.layers.len() {
             let rgba_layer = &self.layers[layer];
             let mut rgb_layer = ImageBuffer::new(self.width, self.height);
             
@@ -125,21 +235,82 @@ impl Canvas {
     }
 }
 
+/// Which image ends up in `FrameSet::composite`
+#[derive(Debug, Clone, Copy)]
+pub enum FrameView {
+    /// All visible layers with their opacity
+    Composite,
+    /// A single layer over white, regardless of the visibility of the others
+    Layer(usize),
+    /// The normal composite with one layer's opacity multiplied by `opacity`
+    Dimmed { layer: usize, opacity: f32 },
+}
+
 pub struct FrameSet {
-    pub layer0: RgbImage,
-    pub layer1: RgbImage,
+    pub layers: Vec<RgbImage>, // Each layer on its own over white, indexed like `Canvas::layers`
     pub composite: RgbImage,
 }
 
+impl FrameSet {
+    /// Enlarge every image of the frame with `filter`
+    pub fn upscale(&self, filter: Upscale) -> Result<FrameSet> {
+        Ok(FrameSet {
+            layers: self.layers.iter().map(|layer| upscale(layer, filter)).collect::<Result<_>>()?,
+            composite: upscale(&self.composite, filter)?,
+        })
+    }
+}
+
+/// A frame together with how long it should stay on screen, in seconds
+pub struct TimedFrame {
+    pub frame: FrameSet,
+    pub duration: f64,
+}
+
+/// Largest factor accepted by `Renderer::with_scale`
+pub const MAX_SCALE: u32 = 8;
+
 impl Renderer {
     pub fn new(width: u32, height: u32) -> Self {
+        Self::with_layers(width, height, NEO_LAYERS)
+    }
+
+    pub fn with_layers(width: u32, height: u32, layer_count: usize) -> Self {
+        Self::create(width, height, layer_count, 1)
+    }
+
+    /// Renderer whose canvas is `scale` times the replay size. Coordinates and brush
+    /// widths are scaled before rasterizing, so strokes stay sharp instead of being
+    /// upscaled pixels. `scale` must be between 1 and `MAX_SCALE`.
+    pub fn with_scale(width: u32, height: u32, scale: u32) -> Result<Self> {
+        if !(1..=MAX_SCALE).contains(&scale) {
+            bail!("Scale must be between 1 and {}, got {}", MAX_SCALE, scale);
+        }
+        if width.checked_mul(scale).is_none() || height.checked_mul(scale).is_none() {
+            bail!("Canvas of {}x{} is too large to render at scale {}", width, height, scale);
+        }
+        Ok(Self::create(width, height, NEO_LAYERS, scale))
+    }
+
+    // `scale` is at least 1 and small enough for the scaled size to fit in u32
+    fn create(width: u32, height: u32, layer_count: usize, scale: u32) -> Self {
         let mut renderer = Self {
-            canvas: Canvas::new(width, height),
+            canvas: Canvas::with_layers(width * scale, height * scale, layer_count),
             state: DrawingState::default(),
             round_data: Vec::new(),
             tone_data: Vec::new(),
             arial_font: Self::load_arial_font(),
             clipboard: None,
+            stroke_animation: None,
+            stroke_progress: 0.0,
+            pending_frames: Vec::new(),
+            show_cursor: false,
+            cursor: None,
+            view: FrameView::Composite,
+            scale,
+            upscale: None,
+            time_limit: None,
+            warnings: Vec::new(),
         };
         renderer.init_round_data();
         renderer.init_tone_data();
@@ -147,10 +318,11 @@ impl Renderer {
     }
 
     fn init_round_data(&mut self) {
-        // Initialize round data for brush sizes 1-30
-        self.round_data = vec![Vec::new(); 31]; // Index 0 unused, 1-30 for brush sizes
+        // Initialize round data for brush sizes 1-30, scaled up for high resolution rendering
+        let max_size = 30 * self.scale as usize;
+        self.round_data = vec![Vec::new(); max_size + 1]; // Index 0 unused
         
-        for r in 1..=30 {
+        for r in 1..=max_size {
             let mut mask = vec![0u8; r * r];
             let mut index = 0;
             
@@ -169,6 +341,11 @@ impl Renderer {
             self.round_data[r] = mask;
         }
         
+        // The adjustments below reproduce NEO's small brush shapes and only apply at 1x
+        if self.scale > 1 {
+            return;
+        }
+
         // Apply the specific pixel adjustments from the original code
         if self.round_data.len() > 3 {
             let mask = &mut self.round_data[3];
@@ -195,31 +372,43 @@ impl Renderer {
         }
     }
     
+    #[cfg(feature = "system-fonts")]
     fn load_arial_font() -> Option<FontRef<'static>> {
-        // Try to load Arial from the system
-        let source = SystemSource::new();
-        
-        // Try Arial first, then fallback to other sans-serif fonts
-        let family_names = [
-            FamilyName::Title("Arial".into()),
-            FamilyName::SansSerif,
-        ];
-        
-        for family_name in &family_names {
-            if let Ok(handle) = source.select_best_match(&[family_name.clone()], &Properties::new()) {
-                if let Ok(font_data) = handle.load() {
-                    // Convert font data to static lifetime by leaking it
-                    // This is acceptable since we only load one font for the entire program
-                    if let Some(data_vec) = font_data.copy_font_data() {
-                        let font_bytes: &'static [u8] = Box::leak((*data_vec).clone().into_boxed_slice());
-                        if let Ok(font) = FontRef::try_from_slice(font_bytes) {
-                            return Some(font);
+        // Font lookup is slow and the data is leaked to get a static lifetime,
+        // so it happens once per process and is shared by every renderer
+        static FONT: OnceLock<Option<FontRef<'static>>> = OnceLock::new();
+
+        FONT.get_or_init(|| {
+            // Try to load Arial from the system
+            let source = SystemSource::new();
+
+            // Try Arial first, then fallback to other sans-serif fonts
+            let family_names = [
+                FamilyName::Title("Arial".into()),
+                FamilyName::SansSerif,
+            ];
+
+            for family_name in &family_names {
+                if let Ok(handle) = source.select_best_match(std::slice::from_ref(family_name), &Properties::new()) {
+                    if let Ok(font_data) = handle.load() {
+                        if let Some(data_vec) = font_data.copy_font_data() {
+                            let font_bytes: &'static [u8] = Box::leak((*data_vec).clone().into_boxed_slice());
+                            if let Ok(font) = FontRef::try_from_slice(font_bytes) {
+                                return Some(font);
+                            }
                         }
                     }
                 }
             }
-        }
-        
+
+            None
+        })
+        .clone()
+    }
+
+    // Without system fonts, text falls back to the built-in bitmap font
+    #[cfg(not(feature = "system-fonts"))]
+    fn load_arial_font() -> Option<FontRef<'static>> {
         None
     }
 
@@ -245,10 +434,8 @@ impl Renderer {
         // Alpha table from original JavaScript
         let alpha_table = [23, 47, 69, 92, 114, 114, 114, 138, 161, 184, 184, 207, 230, 230, 253];
         
-        for i in 0..alpha_table.len() {
-            if alpha < alpha_table[i] {
-                return &self.tone_data[i];
-            }
+        if let Some(i) = alpha_table.iter().position(|&threshold| alpha < threshold) {
+            return &self.tone_data[i];
         }
         
         // Return last pattern if alpha is >= all thresholds
@@ -265,15 +452,15 @@ impl Renderer {
                 } else {
                     a1 = (2.0_f64 * a1).sqrt() / 16.0;
                 }
-                a1 = a1.min(1.0).max(0.0);
+                a1 = a1.clamp(0.0, 1.0);
             }
             AlphaType::Fill => {
                 a1 = -0.00056 * a1 + 0.0042 / (1.0 - a1) - 0.0042;
-                a1 = (a1 * 10.0).min(1.0).max(0.0);
+                a1 = (a1 * 10.0).clamp(0.0, 1.0);
             }
             AlphaType::Brush => {
                 a1 = -0.00056 * a1 + 0.0042 / (1.0 - a1) - 0.0042;
-                a1 = a1.min(1.0).max(0.0);
+                a1 = a1.clamp(0.0, 1.0);
             }
         }
         
@@ -295,25 +482,177 @@ impl Renderer {
         
         // Clear canvas initially
         self.canvas.clear();
-        frames.push(FrameSet {
-            layer0: self.canvas.get_layer_as_rgb(0).unwrap(),
-            layer1: self.canvas.get_layer_as_rgb(1).unwrap(),
-            composite: self.canvas.composite(),
-        });
+        frames.push(self.capture_frame()?);
 
         for action in &pch.actions {
             self.execute_action(action)?;
-            frames.push(FrameSet {
-                layer0: self.canvas.get_layer_as_rgb(0).unwrap(),
-                layer1: self.canvas.get_layer_as_rgb(1).unwrap(),
-                composite: self.canvas.composite(),
+            frames.append(&mut self.pending_frames);
+            frames.push(self.capture_frame()?);
+        }
+
+        Ok(frames)
+    }
+
+    /// Render like `render_frame_by_frame`, assigning each frame a display time from `timeline`.
+    ///
+    /// The pause before an action is spent on the frame preceding it; an action's drawing
+    /// time is split evenly across its intermediate frames and its final frame.
+    /// `timeline` must have one entry per action of `pch`, as built from the same file.
+    pub fn render_paced(&mut self, pch: &PchFile, timeline: &Timeline) -> Result<Vec<TimedFrame>> {
+        if timeline.entries.len() != pch.actions.len() {
+            bail!(
+                "Timeline has {} entries but the replay has {} actions; build it after fix_actions",
+                timeline.entries.len(),
+                pch.actions.len()
+            );
+        }
+        let mut frames = Vec::new();
+
+        self.canvas.clear();
+        frames.push(TimedFrame {
+            frame: self.capture_frame()?,
+            duration: 0.0,
+        });
+
+        for (action, entry) in pch.actions.iter().zip(&timeline.entries) {
+            self.execute_action(action)?;
+
+            if let Some(last) = frames.last_mut() {
+                last.duration += entry.idle;
+            }
+
+            let count = self.pending_frames.len() + 1;
+            let duration = entry.duration / count as f64;
+            for frame in self.pending_frames.drain(..) {
+                frames.push(TimedFrame { frame, duration });
+            }
+            frames.push(TimedFrame {
+                frame: self.capture_frame()?,
+                duration,
             });
         }
 
         Ok(frames)
     }
 
+    /// Fail with `LimitExceeded::RenderTime` once rendering runs more than `limit` past now,
+    /// also in the middle of a long stroke or flood fill.
+    /// `None` removes the limit. The clock is not read without a limit, so this is
+    /// safe to leave unset on targets without `Instant`, such as wasm32.
+    pub fn set_time_limit(&mut self, limit: Option<Duration>) {
+        self.time_limit = limit.map(|limit| (Instant::now() + limit, limit));
+    }
+
+    /// Enlarge emitted frames with `filter`. Fails if the filter's factor is out of
+    /// range or the upscaled canvas would be too large.
+    pub fn set_upscale(&mut self, filter: Option<Upscale>) -> Result<()> {
+        if let Some(filter) = filter {
+            filter.output_size(self.canvas.width, self.canvas.height)?;
+        }
+        self.upscale = filter;
+        Ok(())
+    }
+
+    /// Execute a single action on the current canvas
+    pub fn step(&mut self, action: &[ActionValue]) -> Result<()> {
+        self.execute_action(action)?;
+        self.pending_frames.clear();
+        Ok(())
+    }
+
+    /// Replay the first `count` actions of `pch` onto a cleared canvas
+    pub fn render_to(&mut self, pch: &PchFile, count: usize) -> Result<()> {
+        self.canvas.clear();
+
+        for action in pch.actions.iter().take(count) {
+            self.step(action)?;
+        }
+
+        Ok(())
+    }
+
+    fn capture_frame(&self) -> Result<FrameSet> {
+        let mut frame = FrameSet {
+            layers: (0..self.canvas.layer_count())
+                .filter_map(|layer| self.canvas.get_layer_as_rgb(layer))
+                .collect(),
+            composite: self.canvas.render_view(self.view),
+        };
+
+        if let (true, Some(cursor)) = (self.show_cursor, &self.cursor) {
+            for layer in &mut frame.layers {
+                cursor.draw(layer);
+            }
+            cursor.draw(&mut frame.composite);
+        }
+
+        if let Some(filter) = self.upscale {
+            frame = frame.upscale(filter)?;
+        }
+
+        Ok(frame)
+    }
+
+    fn move_cursor(&mut self, x: i32, y: i32, line_type: Option<LineType>) {
+        if self.show_cursor {
+            self.cursor = Some(Cursor {
+                x,
+                y,
+                // Brushes larger than the biggest mask are drawn at that size
+                width: self.state.current_width.min((self.round_data.len() - 1) as f64),
+                color: self.state.current_color.clone(),
+                line_type,
+            });
+        }
+    }
+
+    fn begin_stroke(&mut self) {
+        self.stroke_progress = 0.0;
+    }
+
+    // Called after each dab along a stroke; `step` is the distance moved since the previous dab
+    fn advance_stroke(&mut self, step: f64) -> Result<()> {
+        let Some(animation) = self.stroke_animation else {
+            return Ok(());
+        };
+
+        let threshold = match animation {
+            StrokeAnimation::Points(n) => {
+                // Segment joints are stamped twice; only count dabs that moved
+                if step > 0.0 {
+                    self.stroke_progress += 1.0;
+                }
+                n.max(1) as f64
+            }
+            StrokeAnimation::Distance(d) => {
+                // Measured in replay pixels, not scaled canvas pixels
+                self.stroke_progress += step / self.scale as f64;
+                d.max(1.0)
+            }
+        };
+
+        if self.stroke_progress >= threshold {
+            self.stroke_progress -= threshold;
+            let frame = self.capture_frame()?;
+            self.pending_frames.push(frame);
+        }
+        Ok(())
+    }
+
+    // Fails once the deadline of `set_time_limit` has passed; called before each
+    // action and inside the loops of strokes and flood fills
+    fn check_time(&self) -> Result<()> {
+        if let Some((deadline, limit)) = self.time_limit {
+            if Instant::now() > deadline {
+                return Err(LimitExceeded::RenderTime(limit).into());
+            }
+        }
+        Ok(())
+    }
+
     fn execute_action(&mut self, action: &[ActionValue]) -> Result<()> {
+        self.check_time()?;
+
         if action.is_empty() {
             return Ok(());
         }
@@ -336,10 +675,7 @@ impl Renderer {
             "paste" => self.paste(action)?,
             "merge" => self.merge(action)?,
             "restore" => self.restore(action)?,
-            _ => {
-                // Unknown command, skip
-                println!("Unknown command: {}", command);
-            }
+            _ => self.warn(format!("Unknown command: {}", command)),
         }
 
         Ok(())
@@ -351,8 +687,10 @@ impl Renderer {
 
     fn erase_all(&mut self, action: &[ActionValue]) -> Result<()> {
         if action.len() >= 2 {
-            if let ActionValue::Number(layer) = action[1] {
-                self.canvas.clear_layer(layer as usize);
+            match action[1] {
+                ActionValue::Number(layer) => self.canvas.clear_layer(layer as usize),
+                ActionValue::Integer(layer) => self.canvas.clear_layer(layer as usize),
+                _ => {}
             }
         }
         Ok(())
@@ -366,15 +704,17 @@ impl Renderer {
         // Parse layer and drawing state
         let layer = match action[1] {
             ActionValue::Number(n) => n as usize,
+            ActionValue::Integer(i) => i as usize,
             _ => return Ok(()),
         };
 
-        if layer >= 2 {
+        if layer >= self.canvas.layer_count() {
             return Ok(());
         }
 
         // Update drawing state from action
         self.update_drawing_state_from_action(action);
+        self.begin_stroke();
 
         // Parse line type and coordinates
         let line_type = match action.get(11) {
@@ -385,12 +725,12 @@ impl Renderer {
         // Draw points from action data
         let mut i = 12;
         while i + 3 < action.len() {
-            let x0 = self.get_number(&action[i])?;
-            let y0 = self.get_number(&action[i + 1])?;
-            let x1 = self.get_number(&action[i + 2])?;
-            let y1 = self.get_number(&action[i + 3])?;
+            let x0 = self.scale_point(self.get_number(&action[i])?);
+            let y0 = self.scale_point(self.get_number(&action[i + 1])?);
+            let x1 = self.scale_point(self.get_number(&action[i + 2])?);
+            let y1 = self.scale_point(self.get_number(&action[i + 3])?);
 
-            self.draw_line_segment(layer, x0 as u32, y0 as u32, x1 as u32, y1 as u32, &line_type);
+            self.draw_line_segment(layer, x0 as i32, y0 as i32, x1 as i32, y1 as i32, &line_type)?;
             i += 2;
         }
 
@@ -404,32 +744,80 @@ impl Renderer {
 
         let layer = match action[1] {
             ActionValue::Number(n) => n as usize,
+            ActionValue::Integer(i) => i as usize,
             _ => return Ok(()),
         };
 
-        if layer >= 2 {
+        if layer >= self.canvas.layer_count() {
             return Ok(());
         }
 
         self.update_drawing_state_from_action(action);
+        self.begin_stroke();
 
         let line_type = match action.get(11) {
             Some(ActionValue::Number(n)) => LineType::from(*n as i64),
             _ => LineType::Pen,
         };
 
-        let x0 = self.get_number(&action[12])? as u32;
-        let y0 = self.get_number(&action[13])? as u32;
-        let x1 = self.get_number(&action[14])? as u32;
-        let y1 = self.get_number(&action[15])? as u32;
+        let x0 = self.scale_point(self.get_number(&action[12])?) as i32;
+        let y0 = self.scale_point(self.get_number(&action[13])?) as i32;
+        let x1 = self.scale_point(self.get_number(&action[14])?) as i32;
+        let y1 = self.scale_point(self.get_number(&action[15])?) as i32;
 
-        self.draw_line_segment(layer, x0, y0, x1, y1, &line_type);
-        Ok(())
+        self.draw_line_segment(layer, x0, y0, x1, y1, &line_type)
     }
 
-    fn draw_bezier(&mut self, _action: &[ActionValue]) -> Result<()> {
-        // Simplified bezier - just skip for now
-        println!("Bezier curves not implemented yet");
+    fn draw_bezier(&mut self, action: &[ActionValue]) -> Result<()> {
+        // Bezier action format: ["bezier", layer, state..., lineType, x0, y0, x1, y1, x2, y2, x3, y3]
+        if action.len() < 20 {
+            return Ok(());
+        }
+
+        let layer = match action[1] {
+            ActionValue::Number(n) => n as usize,
+            ActionValue::Integer(i) => i as usize,
+            _ => return Ok(()),
+        };
+
+        if layer >= self.canvas.layer_count() {
+            return Ok(());
+        }
+
+        self.update_drawing_state_from_action(action);
+        self.begin_stroke();
+
+        let line_type = match action.get(11) {
+            Some(ActionValue::Number(n)) => LineType::from(*n as i64),
+            _ => LineType::Pen,
+        };
+
+        let mut points = [(0.0, 0.0); 4];
+        for (k, point) in points.iter_mut().enumerate() {
+            *point = (
+                self.scale_point(self.get_number(&action[12 + k * 2])?),
+                self.scale_point(self.get_number(&action[13 + k * 2])?),
+            );
+        }
+        let [(x0, y0), (x1, y1), (x2, y2), (x3, y3)] = points;
+
+        // Subdivide the cubic curve into short segments based on its control polygon length
+        let polygon_length = ((x1 - x0).hypot(y1 - y0) + (x2 - x1).hypot(y2 - y1) + (x3 - x2).hypot(y3 - y2)).min(10_000.0);
+        let steps = (polygon_length.ceil() as usize).max(1);
+
+        let mut prev = (x0, y0);
+        for step in 1..=steps {
+            let t = step as f64 / steps as f64;
+            let u = 1.0 - t;
+            let x = u * u * u * x0 + 3.0 * u * u * t * x1 + 3.0 * u * t * t * x2 + t * t * t * x3;
+            let y = u * u * u * y0 + 3.0 * u * u * t * y1 + 3.0 * u * t * t * y2 + t * t * t * y3;
+
+            if (x as i32, y as i32) != (prev.0 as i32, prev.1 as i32) || step == steps {
+                self.draw_line_segment(layer, prev.0 as i32, prev.1 as i32, x as i32, y as i32, &line_type)?;
+                prev = (x, y);
+            }
+        }
+
         Ok(())
     }
 
@@ -440,6 +828,7 @@ impl Renderer {
 
         let layer = match action[1] {
             ActionValue::Number(n) => n as usize,
+            ActionValue::Integer(i) => i as usize,
             _ => return Ok(()),
         };
 
@@ -472,7 +861,7 @@ impl Renderer {
         }
 
         if let Ok(width) = self.get_number(&action[9]) {
-            self.state.current_width = width;
+            self.state.current_width = self.scale_length(width);
         }
 
         if let Ok(mask_type) = self.get_number(&action[10]) {
@@ -480,12 +869,17 @@ impl Renderer {
         }
 
         // Skip color/mask parameters (indices 2-10)
-        let x = self.get_number(&action[11])? as u32;
-        let y = self.get_number(&action[12])? as u32;
-        let width = self.get_number(&action[13])? as u32;
-        let height = self.get_number(&action[14])? as u32;
+        let x = self.scale_length(self.get_number(&action[11])?) as i32;
+        let y = self.scale_length(self.get_number(&action[12])?) as i32;
+        let width = self.scale_length(self.get_number(&action[13])?) as u32;
+        let height = self.scale_length(self.get_number(&action[14])?) as u32;
         let fill_type = self.get_number(&action[15])? as u32;
 
+        self.move_cursor(
+            x.saturating_add((width / 2).min(i32::MAX as u32) as i32),
+            y.saturating_add((height / 2).min(i32::MAX as u32) as i32),
+            None,
+        );
         self.do_fill(layer, x, y, width, height, fill_type)
     }
 
@@ -496,13 +890,15 @@ impl Renderer {
 
         let layer = match action[1] {
             ActionValue::Number(n) => n as usize,
+            ActionValue::Integer(i) => i as usize,
             _ => return Ok(()),
         };
 
-        let x = self.get_number(&action[2])? as i32;
-        let y = self.get_number(&action[3])? as i32;
+        let x = self.scale_point(self.get_number(&action[2])?) as i32;
+        let y = self.scale_point(self.get_number(&action[3])?) as i32;
         let fill_color = self.get_number(&action[4])? as u32;
 
+        self.move_cursor(x, y, None);
         self.do_flood_fill(layer, x, y, fill_color)
     }
 
@@ -514,24 +910,31 @@ impl Renderer {
 
         let layer = match action[1] {
             ActionValue::Number(n) => n as usize,
+            ActionValue::Integer(i) => i as usize,
             _ => return Ok(()),
         };
 
-        if layer >= 2 {
+        if layer >= self.canvas.layer_count() {
             return Ok(());
         }
 
-        let x = self.get_number(&action[2])? as u32;
-        let y = self.get_number(&action[3])? as u32;
+        let x = self.scale_length(self.get_number(&action[2])?) as u32;
+        let y = self.scale_length(self.get_number(&action[3])?) as u32;
         let color = self.get_number(&action[4])? as u32;
-        let alpha = self.get_number(&action[5])? as f64;
+        let alpha = self.get_number(&action[5])?;
         
         let text = match &action[6] {
             ActionValue::String(s) => s.clone(),
             _ => return Ok(()),
         };
         
-        let size = self.parse_font_size(&action[7])? as u32;
+        let size = self.parse_font_size(&action[7]).clamp(0.0, MAX_FONT_SIZE);
+        let size = self.scale_length(size) as u32;
+        // `y` is the baseline, so text starting below the canvas can still reach into it
+        if x >= self.canvas.width || y.saturating_sub(size) >= self.canvas.height {
+            return Ok(());
+        }
+        self.move_cursor(x as i32, y as i32, None);
         
         // Use Arial font if available, otherwise fallback to bitmap
         if let Some(font) = self.arial_font.clone() {
@@ -550,14 +953,16 @@ impl Renderer {
 
         let layer = match action[1] {
             ActionValue::Number(n) => n as usize,
+            ActionValue::Integer(i) => i as usize,
             _ => return Ok(()),
         };
 
-        let x = self.get_number(&action[2])? as u32;
-        let y = self.get_number(&action[3])? as u32;
-        let width = self.get_number(&action[4])? as u32;
-        let height = self.get_number(&action[5])? as u32;
+        let x = self.scale_length(self.get_number(&action[2])?) as u32;
+        let y = self.scale_length(self.get_number(&action[3])?) as u32;
+        let width = self.scale_length(self.get_number(&action[4])?) as u32;
+        let height = self.scale_length(self.get_number(&action[5])?) as u32;
 
+        self.move_cursor(x.min(i32::MAX as u32) as i32, y.min(i32::MAX as u32) as i32, None);
         self.do_copy(layer, x, y, width, height)
     }
 
@@ -568,16 +973,20 @@ impl Renderer {
 
         let layer = match action[1] {
             ActionValue::Number(n) => n as usize,
+            ActionValue::Integer(i) => i as usize,
             _ => return Ok(()),
         };
 
-        let x = self.get_number(&action[2])? as u32;
-        let y = self.get_number(&action[3])? as u32;
-        let width = self.get_number(&action[4])? as u32;
-        let height = self.get_number(&action[5])? as u32;
-        let dx = self.get_number(&action[6])? as i32;
-        let dy = self.get_number(&action[7])? as i32;
+        let x = self.scale_length(self.get_number(&action[2])?) as u32;
+        let y = self.scale_length(self.get_number(&action[3])?) as u32;
+        let width = self.scale_length(self.get_number(&action[4])?) as u32;
+        let height = self.scale_length(self.get_number(&action[5])?) as u32;
+        let dx = self.scale_length(self.get_number(&action[6])?) as i32;
+        let dy = self.scale_length(self.get_number(&action[7])?) as i32;
 
+        let dest_x = (x as i64 + dx as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        let dest_y = (y as i64 + dy as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        self.move_cursor(dest_x, dest_y, None);
         self.do_paste(layer, x, y, width, height, dx, dy)
     }
 
@@ -588,19 +997,21 @@ impl Renderer {
 
         let layer = match action[1] {
             ActionValue::Number(n) => n as usize,
+            ActionValue::Integer(i) => i as usize,
             _ => return Ok(()),
         };
 
-        let x = self.get_number(&action[2])? as u32;
-        let y = self.get_number(&action[3])? as u32;
-        let width = self.get_number(&action[4])? as u32;
-        let height = self.get_number(&action[5])? as u32;
+        let x = self.scale_length(self.get_number(&action[2])?) as u32;
+        let y = self.scale_length(self.get_number(&action[3])?) as u32;
+        let width = self.scale_length(self.get_number(&action[4])?) as u32;
+        let height = self.scale_length(self.get_number(&action[5])?) as u32;
 
+        self.move_cursor(x.min(i32::MAX as u32) as i32, y.min(i32::MAX as u32) as i32, None);
         self.do_merge(layer, x, y, width, height)
     }
 
     fn restore(&mut self, _action: &[ActionValue]) -> Result<()> {
-        println!("Restore not implemented yet");
+        self.warn("Restore is not supported yet".to_string());
         Ok(())
     }
 
@@ -635,7 +1046,7 @@ impl Renderer {
             }
 
             if let Ok(width) = self.get_number(&action[9]) {
-                self.state.current_width = width;
+                self.state.current_width = self.scale_length(width);
             }
 
             if let Ok(mask_type) = self.get_number(&action[10]) {
@@ -644,47 +1055,71 @@ impl Renderer {
         }
     }
 
-    fn draw_line_segment(&mut self, layer: usize, x0: u32, y0: u32, x1: u32, y1: u32, line_type: &LineType) {
+    fn draw_line_segment(&mut self, layer: usize, x0: i32, y0: i32, x1: i32, y1: i32, line_type: &LineType) -> Result<()> {
+        // Parts of the segment further off the canvas than a dab can reach draw nothing;
+        // clip them so stray coordinates cannot make the walk below arbitrarily long
+        let margin = self.round_data.len() as f64 + 1.0;
+        let bounds = (-margin, -margin, self.canvas.width as f64 + margin, self.canvas.height as f64 + margin);
+        let Some((x0, y0, x1, y1)) = clip_segment((x0 as f64, y0 as f64, x1 as f64, y1 as f64), bounds) else {
+            return Ok(());
+        };
+
         // Simple line drawing using Bresenham's algorithm
-        let mut curr_x = x0 as i32;
-        let mut curr_y = y0 as i32;
-        let end_x = x1 as i32;
-        let end_y = y1 as i32;
-        let stroke_x = x0; // Store original stroke start coordinates
-        let stroke_y = y0;
+        let mut curr_x = x0;
+        let mut curr_y = y0;
+        let end_x = x1;
+        let end_y = y1;
+        let stroke_x = x0.max(0) as u32; // Store original stroke start coordinates
+        let stroke_y = y0.max(0) as u32;
 
         let dx = (end_x - curr_x).abs();
         let dy = (end_y - curr_y).abs();
         let sx = if curr_x < end_x { 1 } else { -1 };
         let sy = if curr_y < end_y { 1 } else { -1 };
         let mut err = dx - dy;
+        let mut step = 0.0;
+        let mut index = 0;
 
         loop {
-            if curr_x >= 0 && curr_y >= 0 && curr_x < self.canvas.width as i32 && curr_y < self.canvas.height as i32 {
-                self.draw_point_with_origin(layer, curr_x as u32, curr_y as u32, stroke_x, stroke_y, line_type);
+            let at_end = curr_x == end_x && curr_y == end_y;
+
+            // When scaled, stamp every `scale` pixels so dabs overlap as much as at 1x
+            if index % TIME_CHECK_INTERVAL == 0 {
+                self.check_time()?;
+            }
+            if index % self.scale == 0 || at_end {
+                if curr_x >= 0 && curr_y >= 0 && curr_x < self.canvas.width as i32 && curr_y < self.canvas.height as i32 {
+                    self.draw_point_with_origin(layer, curr_x as u32, curr_y as u32, stroke_x, stroke_y, line_type);
+                }
+                self.move_cursor(curr_x, curr_y, Some(*line_type));
+                self.advance_stroke(step)?;
+                step = 0.0;
             }
+            index += 1;
 
-            if curr_x == end_x && curr_y == end_y {
+            if at_end {
                 break;
             }
 
             let e2 = 2 * err;
+            let mut moved_x = false;
+            let mut moved_y = false;
             if e2 > -dy {
                 err -= dy;
                 curr_x += sx;
+                moved_x = true;
             }
             if e2 < dx {
                 err += dx;
                 curr_y += sy;
+                moved_y = true;
             }
+            step += if moved_x && moved_y { std::f64::consts::SQRT_2 } else { 1.0 };
         }
-    }
 
-    fn draw_point(&mut self, layer: usize, x: u32, y: u32, line_type: &LineType) {
-        // For backward compatibility, use current point as stroke origin
-        self.draw_point_with_origin(layer, x, y, x, y, line_type);
+        Ok(())
     }
-    
+
     pub fn draw_point_with_origin(&mut self, layer: usize, x: u32, y: u32, x0: u32, y0: u32, line_type: &LineType) {
         match line_type {
             LineType::Pen => self.set_pen_point(layer, x, y),
@@ -700,7 +1135,7 @@ impl Renderer {
     
     fn set_pen_point(&mut self, layer: usize, x: u32, y: u32) {
         let d = self.state.current_width as usize;
-        let d = d.clamp(1, 30);
+        let d = d.clamp(1, self.round_data.len() - 1);
         let r = (d as f64 / 2.0).floor() as usize;
         
         if d >= self.round_data.len() || self.round_data[d].is_empty() {
@@ -774,7 +1209,7 @@ impl Renderer {
     
     fn set_brush_point(&mut self, layer: usize, x: u32, y: u32) {
         let d = self.state.current_width as usize;
-        let d = d.clamp(1, 30);
+        let d = d.clamp(1, self.round_data.len() - 1);
         let r = (d as f64 / 2.0).floor() as usize;
         
         if d >= self.round_data.len() || self.round_data[d].is_empty() {
@@ -848,7 +1283,7 @@ impl Renderer {
     
     fn set_tone_point(&mut self, layer: usize, x: u32, y: u32, _x0: u32, _y0: u32) {
         let d = self.state.current_width as usize;
-        let d = d.clamp(1, 30);
+        let d = d.clamp(1, self.round_data.len() - 1);
         let r = (d as f64 / 2.0).floor() as usize;
         
         if d >= self.round_data.len() || self.round_data[d].is_empty() {
@@ -881,8 +1316,9 @@ impl Renderer {
                         // Use original stroke position plus brush offset (like JavaScript)
                         let offset_x = pixel_x - start_x;
                         let offset_y = pixel_y - start_y;
-                        let pattern_x = ((x as i32 + offset_x) as usize) % 4;
-                        let pattern_y = ((y as i32 + offset_y) as usize) % 4;
+                        // Dither cells are scaled with the canvas to keep the tone look
+                        let pattern_x = ((x as i32 + offset_x) as usize / self.scale as usize) % 4;
+                        let pattern_y = ((y as i32 + offset_y) as usize / self.scale as usize) % 4;
                         let pattern_index = pattern_y * 4 + pattern_x;
                         
                         // Apply tone if the dithering pattern allows it
@@ -902,7 +1338,7 @@ impl Renderer {
     
     fn set_eraser_point(&mut self, layer: usize, x: u32, y: u32) {
         let d = self.state.current_width as usize;
-        let d = d.clamp(1, 30);
+        let d = d.clamp(1, self.round_data.len() - 1);
         let r = (d as f64 / 2.0).floor() as usize;
         
         if d >= self.round_data.len() || self.round_data[d].is_empty() {
@@ -937,6 +1373,7 @@ impl Renderer {
         }
     }
     
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_simple_text(&mut self, layer: usize, x: u32, y: u32, text: &str, color: u32, alpha: f64, size: u32) {
         // Extract RGB from color
         let r = (color & 0xff) as u8;
@@ -946,7 +1383,7 @@ impl Renderer {
         
         // Simple 8x8 bitmap font for basic ASCII characters
         // Each character is represented as an 8x8 bitmap
-        let font_data = self.get_simple_font_data();
+        let font_data = Self::simple_font_data();
         
         let char_width = 8;
         let _char_height = 8;
@@ -958,7 +1395,7 @@ impl Renderer {
             if let Some(bitmap) = font_data.get(&ch) {
                 self.draw_character_bitmap(layer, char_x, y, bitmap, r, g, b, final_alpha, scale);
             }
-            char_x += char_width * scale + 1; // Add 1 pixel spacing between chars
+            char_x = char_x.saturating_add(char_width * scale + 1); // Add 1 pixel spacing between chars
             
             // Stop if we're going off the canvas
             if char_x >= self.canvas.width {
@@ -967,6 +1404,7 @@ impl Renderer {
         }
     }
     
+    #[allow(clippy::too_many_arguments)]
     fn draw_arial_text(&mut self, layer: usize, x: u32, y: u32, text: &str, color: u32, alpha: f64, size: u32, font: FontRef<'static>) {
         // Extract RGB from color
         let r = (color & 0xff) as u8;
@@ -982,6 +1420,9 @@ impl Renderer {
         let mut cursor = point(x as f32, y as f32);
         
         for ch in text.chars() {
+            if cursor.x >= self.canvas.width as f32 {
+                break; // The rest of the line is off the canvas
+            }
             let glyph_id = font.glyph_id(ch);
             let glyph = glyph_id.with_scale_and_position(scale, cursor);
             glyphs.push(glyph);
@@ -1037,7 +1478,7 @@ impl Renderer {
         }
     }
     
-    fn do_fill(&mut self, layer: usize, x: u32, y: u32, width: u32, height: u32, fill_type: u32) -> Result<()> {
+    fn do_fill(&mut self, layer: usize, x: i32, y: i32, width: u32, height: u32, fill_type: u32) -> Result<()> {
         if layer >= self.canvas.layers.len() {
             return Ok(());
         }
@@ -1047,16 +1488,14 @@ impl Renderer {
         let b1 = self.state.current_color.b;
         let a1 = self.get_alpha(AlphaType::Fill);
 
-        // Clamp fill area to canvas bounds
-        let canvas_width = self.canvas.width;
-        let canvas_height = self.canvas.height;
-        let end_x = (x + width).min(canvas_width);
-        let end_y = (y + height).min(canvas_height);
+        // Clamp fill area to canvas bounds; the mask stays relative to the unclipped shape
+        let (start_x, end_x) = clip_span(x, width, self.canvas.width);
+        let (start_y, end_y) = clip_span(y, height, self.canvas.height);
 
-        for j in y..end_y {
-            for i in x..end_x {
-                let local_x = i - x;
-                let local_y = j - y;
+        for j in start_y..end_y {
+            for i in start_x..end_x {
+                let local_x = (i as i64 - x as i64) as u32;
+                let local_y = (j as i64 - y as i64) as u32;
                 
                 if self.apply_fill_mask(local_x, local_y, width, height, fill_type) {
                     // Get current pixel
@@ -1113,12 +1552,12 @@ impl Renderer {
 
     fn rect_mask(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
         let d = self.state.current_width as u32;
-        x < d || x > width.saturating_sub(1 + d) || y < d || y > height.saturating_sub(1 + d)
+        x < d || x > width.saturating_sub(d.saturating_add(1)) || y < d || y > height.saturating_sub(d.saturating_add(1))
     }
 
     fn ellipse_fill_mask(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
-        let cx = (width - 1) as f64 / 2.0;
-        let cy = (height - 1) as f64 / 2.0;
+        let cx = width.saturating_sub(1) as f64 / 2.0;
+        let cy = height.saturating_sub(1) as f64 / 2.0;
         let x_norm = (x as f64 - cx) / (cx + 1.0);
         let y_norm = (y as f64 - cy) / (cy + 1.0);
 
@@ -1127,8 +1566,8 @@ impl Renderer {
 
     fn ellipse_mask(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
         let d = self.state.current_width;
-        let cx = (width - 1) as f64 / 2.0;
-        let cy = (height - 1) as f64 / 2.0;
+        let cx = width.saturating_sub(1) as f64 / 2.0;
+        let cy = height.saturating_sub(1) as f64 / 2.0;
 
         if cx <= d || cy <= d {
             return self.ellipse_fill_mask(x, y, width, height);
@@ -1140,12 +1579,7 @@ impl Renderer {
         let x_norm = (x as f64 - cx) / (cx + 1.0);
         let y_norm = (y as f64 - cy) / (cy + 1.0);
 
-        if x_norm * x_norm + y_norm * y_norm < 1.0 {
-            if x2_norm * x2_norm + y2_norm * y2_norm >= 1.0 {
-                return true;
-            }
-        }
-        false
+        x_norm * x_norm + y_norm * y_norm < 1.0 && x2_norm * x2_norm + y2_norm * y2_norm >= 1.0
     }
 
     fn do_flood_fill(&mut self, layer: usize, x: i32, y: i32, fill_color: u32) -> Result<()> {
@@ -1165,7 +1599,7 @@ impl Renderer {
 
         // Get base color at the starting point
         let base_pixel = self.canvas.layers[layer].get_pixel(x, y);
-        let base_color = pixel_to_u32(&base_pixel);
+        let base_color = pixel_to_u32(base_pixel);
 
         // Convert fill_color to RGBA components
         let fill_r = (fill_color & 0xff) as u8;
@@ -1183,7 +1617,12 @@ impl Renderer {
         stack.push((x, y));
         const MAX_STACK_SIZE: usize = 1_000_000;
 
+        let mut steps = 0u32;
         while let Some((px, py)) = stack.pop() {
+            steps = steps.wrapping_add(1);
+            if steps.is_multiple_of(TIME_CHECK_INTERVAL) {
+                self.check_time()?;
+            }
             if stack.len() > MAX_STACK_SIZE {
                 break; // Prevent stack overflow like in original
             }
@@ -1194,7 +1633,7 @@ impl Renderer {
             }
 
             let current_pixel = self.canvas.layers[layer].get_pixel(px, py);
-            let current_color = pixel_to_u32(&current_pixel);
+            let current_color = pixel_to_u32(current_pixel);
 
             // Skip if already filled or not the base color
             if current_color == fill_color || current_color != base_color {
@@ -1208,7 +1647,7 @@ impl Renderer {
             // Extend left
             while x0 > 0 {
                 let left_pixel = self.canvas.layers[layer].get_pixel(x0 - 1, py);
-                let left_color = pixel_to_u32(&left_pixel);
+                let left_color = pixel_to_u32(left_pixel);
                 if left_color != base_color {
                     break;
                 }
@@ -1218,7 +1657,7 @@ impl Renderer {
             // Extend right
             while x1 < width - 1 {
                 let right_pixel = self.canvas.layers[layer].get_pixel(x1 + 1, py);
-                let right_color = pixel_to_u32(&right_pixel);
+                let right_color = pixel_to_u32(right_pixel);
                 if right_color != base_color {
                     break;
                 }
@@ -1257,8 +1696,8 @@ impl Renderer {
         // Clamp region to canvas bounds
         let canvas_width = self.canvas.width;
         let canvas_height = self.canvas.height;
-        let end_x = (x + width).min(canvas_width);
-        let end_y = (y + height).min(canvas_height);
+        let end_x = x.saturating_add(width).min(canvas_width);
+        let end_y = y.saturating_add(height).min(canvas_height);
 
         if x >= canvas_width || y >= canvas_height || end_x <= x || end_y <= y {
             return Ok(()); // Nothing to copy
@@ -1268,12 +1707,12 @@ impl Renderer {
         let actual_height = end_y - y;
 
         // Copy pixel data to clipboard
-        let mut clipboard_data = Vec::with_capacity((actual_width * actual_height) as usize);
+        let mut clipboard_data = Vec::with_capacity(actual_width as usize * actual_height as usize);
         
         for py in y..end_y {
             for px in x..end_x {
                 let pixel = self.canvas.layers[layer].get_pixel(px, py);
-                let packed = pixel_to_u32(&pixel);
+                let packed = pixel_to_u32(pixel);
                 clipboard_data.push(packed);
             }
         }
@@ -1282,6 +1721,7 @@ impl Renderer {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn do_paste(&mut self, layer: usize, x: u32, y: u32, width: u32, height: u32, dx: i32, dy: i32) -> Result<()> {
         if layer >= self.canvas.layers.len() {
             return Ok(());
@@ -1291,33 +1731,31 @@ impl Renderer {
             return Ok(()); // No data to paste
         };
 
-        // Calculate destination position
-        let dest_x = (x as i32 + dx) as u32;
-        let dest_y = (y as i32 + dy) as u32;
+        // The clipboard holds the copied rectangle as clipped to the canvas by do_copy
+        let copied_width = x.saturating_add(width).min(self.canvas.width).saturating_sub(x) as i64;
+        let copied_height = y.saturating_add(height).min(self.canvas.height).saturating_sub(y) as i64;
 
-        // Clamp destination to canvas bounds
-        let canvas_width = self.canvas.width;
-        let canvas_height = self.canvas.height;
-        let end_x = (dest_x + width).min(canvas_width);
-        let end_y = (dest_y + height).min(canvas_height);
+        // Calculate destination position and the part of it inside the canvas
+        let dest_x = x as i64 + dx as i64;
+        let dest_y = y as i64 + dy as i64;
+        let cols = (dest_x.max(0), (dest_x + copied_width).min(self.canvas.width as i64));
+        let rows = (dest_y.max(0), (dest_y + copied_height).min(self.canvas.height as i64));
 
-        if dest_x >= canvas_width || dest_y >= canvas_height || end_x <= dest_x || end_y <= dest_y {
+        if cols.0 >= cols.1 || rows.0 >= rows.1 {
             return Ok(()); // Nothing to paste
         }
 
         // Paste pixel data
-        let mut clipboard_index = 0;
-        for py in dest_y..end_y {
-            for px in dest_x..end_x {
-                if clipboard_index < clipboard_data.len() {
-                    let packed_color = clipboard_data[clipboard_index];
+        for py in rows.0..rows.1 {
+            for px in cols.0..cols.1 {
+                let clipboard_index = ((py - dest_y) * copied_width + (px - dest_x)) as usize;
+                if let Some(&packed_color) = clipboard_data.get(clipboard_index) {
                     let r = (packed_color & 0xff) as u8;
                     let g = ((packed_color >> 8) & 0xff) as u8;
                     let b = ((packed_color >> 16) & 0xff) as u8;
                     let a = ((packed_color >> 24) & 0xff) as u8;
 
-                    self.canvas.layers[layer].put_pixel(px, py, Rgba([r, g, b, a]));
-                    clipboard_index += 1;
+                    self.canvas.layers[layer].put_pixel(px as u32, py as u32, Rgba([r, g, b, a]));
                 }
             }
         }
@@ -1335,8 +1773,8 @@ impl Renderer {
         // Clamp region to canvas bounds
         let canvas_width = self.canvas.width;
         let canvas_height = self.canvas.height;
-        let end_x = (x + width).min(canvas_width);
-        let end_y = (y + height).min(canvas_height);
+        let end_x = x.saturating_add(width).min(canvas_width);
+        let end_y = y.saturating_add(height).min(canvas_height);
 
         if x >= canvas_width || y >= canvas_height || end_x <= x || end_y <= y {
             return Ok(()); // Nothing to merge
@@ -1344,13 +1782,22 @@ impl Renderer {
 
         // Determine destination and source layers
         let dst = layer;
-        let src = if dst == 1 { 0 } else { 1 };
+        let Some(src) = self.canvas.merge_source(dst) else {
+            return Ok(()); // Nothing to merge with
+        };
+
+        // The upper layer of the pair is composited over the lower one
+        let (lower, upper) = if self.canvas.stack_position(src) > self.canvas.stack_position(dst) {
+            (dst, src)
+        } else {
+            (src, dst)
+        };
 
         // Merge pixels from both layers
         for py in y..end_y {
             for px in x..end_x {
-                let pixel0 = self.canvas.layers[0].get_pixel(px, py);
-                let pixel1 = self.canvas.layers[1].get_pixel(px, py);
+                let pixel0 = self.canvas.layers[lower].get_pixel(px, py);
+                let pixel1 = self.canvas.layers[upper].get_pixel(px, py);
 
                 let r0 = pixel0[0] as f64;
                 let g0 = pixel0[1] as f64;
@@ -1384,16 +1831,16 @@ impl Renderer {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn draw_character_bitmap(&mut self, layer: usize, x: u32, y: u32, bitmap: &[u8; 8], r: u8, g: u8, b: u8, alpha: u8, scale: u32) {
-        for row in 0..8 {
-            let byte = bitmap[row];
+        for (row, &byte) in bitmap.iter().enumerate() {
             for col in 0..8 {
                 if (byte >> (7 - col)) & 1 == 1 {
                     // Draw scaled pixel
                     for sy in 0..scale {
                         for sx in 0..scale {
-                            let px = x + col as u32 * scale + sx;
-                            let py = y + row as u32 * scale + sy;
+                            let px = x.saturating_add(col as u32 * scale + sx);
+                            let py = y.saturating_add(row as u32 * scale + sy);
                             
                             if px < self.canvas.width && py < self.canvas.height {
                                 self.canvas.layers[layer].put_pixel(px, py, Rgba([r, g, b, alpha]));
@@ -1405,7 +1852,7 @@ impl Renderer {
         }
     }
     
-    fn get_simple_font_data(&self) -> std::collections::HashMap<char, [u8; 8]> {
+    pub(crate) fn simple_font_data() -> std::collections::HashMap<char, [u8; 8]> {
         use std::collections::HashMap;
         let mut font = HashMap::new();
         
@@ -1470,21 +1917,28 @@ impl Renderer {
         font
     }
 
+    // Map a replay position to the center of the corresponding block of scaled pixels
+    fn scale_point(&self, value: f64) -> f64 {
+        value * self.scale as f64 + (self.scale / 2) as f64
+    }
+
+    // Map a replay length, size or region edge onto the scaled canvas
+    fn scale_length(&self, value: f64) -> f64 {
+        value * self.scale as f64
+    }
+
     fn get_number(&self, value: &ActionValue) -> Result<f64> {
         match value {
             ActionValue::Number(n) => Ok(*n),
             ActionValue::Integer(i) => Ok(*i as f64),
-            _ => {
-                eprintln!("Expected number but got: {:?}", value);
-                bail!("Expected number")
-            },
+            _ => bail!("Expected number but got: {:?}", value),
         }
     }
     
-    fn parse_font_size(&self, value: &ActionValue) -> Result<f64> {
+    fn parse_font_size(&mut self, value: &ActionValue) -> f64 {
         match value {
-            ActionValue::Number(n) => Ok(*n),
-            ActionValue::Integer(i) => Ok(*i as f64),
+            ActionValue::Number(n) => *n,
+            ActionValue::Integer(i) => *i as f64,
             ActionValue::String(s) => {
                 // Parse font size strings like "27px", "16pt", etc.
                 let size_str = s.trim_end_matches("px")
@@ -1492,13 +1946,63 @@ impl Renderer {
                     .trim_end_matches("em")
                     .trim();
                 
-                size_str.parse::<f64>().or_else(|_| {
-                    eprintln!("Could not parse font size: {}", s);
-                    Ok(12.0) // Default font size
+                size_str.parse::<f64>().unwrap_or_else(|_| {
+                    self.warn(format!("Could not parse font size: {}", s));
+                    12.0 // Default font size
                 })
             }
         }
     }
+
+    fn warn(&mut self, message: String) {
+        if self.warnings.len() < MAX_WARNINGS && !self.warnings.contains(&message) {
+            self.warnings.push(message);
+        }
+    }
+}
+
+/// Columns or rows `start..end` of a span at `origin` with `length` that lie on a canvas of `size`
+fn clip_span(origin: i32, length: u32, size: u32) -> (u32, u32) {
+    let start = (origin as i64).clamp(0, size as i64);
+    let end = (origin as i64 + length as i64).clamp(start, size as i64);
+    (start as u32, end as u32)
+}
+
+/// Liang-Barsky clipping of a segment to `(min_x, min_y, max_x, max_y)`, rounded to pixels
+fn clip_segment(segment: (f64, f64, f64, f64), bounds: (f64, f64, f64, f64)) -> Option<(i32, i32, i32, i32)> {
+    let (x0, y0, x1, y1) = segment;
+    let (min_x, min_y, max_x, max_y) = bounds;
+    let inside = |x: f64, y: f64| x >= min_x && x <= max_x && y >= min_y && y <= max_y;
+    if inside(x0, y0) && inside(x1, y1) {
+        return Some((x0 as i32, y0 as i32, x1 as i32, y1 as i32));
+    }
+
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    for (p, q) in [(-dx, x0 - min_x), (dx, max_x - x0), (-dy, y0 - min_y), (dy, max_y - y0)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 || t0.is_nan() || t1.is_nan() {
+        return None;
+    }
+
+    Some((
+        (x0 + t0 * dx) as i32,
+        (y0 + t0 * dy) as i32,
+        (x0 + t1 * dx) as i32,
+        (y0 + t1 * dy) as i32,
+    ))
 }
 
 fn pixel_to_u32(pixel: &Rgba<u8>) -> u32 {
@@ -0,0 +1,123 @@
+//! Summary statistics of a replay, for moderation heuristics and profile pages.
+
+use crate::action::Action;
+use crate::pacing::PacingModel;
+use crate::{Color, LineType, PchFile};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayStats {
+    pub width: u16,
+    pub height: u16,
+    pub actions: usize,
+    pub malformed: usize, // Actions that could not be parsed, not included in `commands`
+    pub commands: BTreeMap<String, usize>, // Actions per command name, unknown commands included
+    pub tools: BTreeMap<String, usize>, // Strokes per line type
+    pub strokes: usize, // freeHand, line and bezier actions
+    pub stroke_points: usize,
+    pub stroke_length: f64, // Total path length in pixels
+    pub colors: Vec<String>, // Distinct "#rrggbb" colors of strokes, fills and text
+    pub widths: Vec<f64>, // Distinct stroke widths, ascending
+    pub layers: BTreeMap<usize, usize>, // Actions per layer
+    pub texts: usize,
+    pub text_chars: usize,
+    pub fills: usize, // Rectangle and ellipse fills
+    pub flood_fills: usize,
+    pub duration: f64, // Estimated drawing time in seconds at normal speed
+}
+
+impl ReplayStats {
+    pub fn from_pch(pch: &PchFile) -> Self {
+        let mut stats = ReplayStats {
+            width: pch.header.width,
+            height: pch.header.height,
+            actions: pch.actions.len(),
+            malformed: 0,
+            commands: BTreeMap::new(),
+            tools: BTreeMap::new(),
+            strokes: 0,
+            stroke_points: 0,
+            stroke_length: 0.0,
+            colors: Vec::new(),
+            widths: Vec::new(),
+            layers: BTreeMap::new(),
+            texts: 0,
+            text_chars: 0,
+            fills: 0,
+            flood_fills: 0,
+            duration: PacingModel::default().timeline(pch, None).total(),
+        };
+        let mut colors = BTreeSet::new();
+        let mut widths = Vec::new();
+
+        for values in &pch.actions {
+            let Some(action) = Action::parse(values) else {
+                stats.malformed += 1;
+                continue;
+            };
+            *stats.commands.entry(action.command().to_string()).or_default() += 1;
+            if let Some(layer) = action.layer() {
+                *stats.layers.entry(layer).or_default() += 1;
+            }
+
+            match &action {
+                Action::FreeHand(stroke) | Action::Line(stroke) | Action::Bezier(stroke) => {
+                    stats.strokes += 1;
+                    stats.stroke_points += stroke.points.len();
+                    stats.stroke_length += stroke.path_length();
+                    *stats.tools.entry(line_type_name(stroke.line_type).to_string()).or_default() += 1;
+                    // Eraser strokes still carry the last pen color, which was never painted
+                    if stroke.line_type != LineType::Eraser {
+                        colors.insert(hex(&stroke.color));
+                    }
+                    if stroke.width.is_finite() {
+                        widths.push(stroke.width);
+                    }
+                }
+                Action::Fill(fill) => {
+                    stats.fills += 1;
+                    colors.insert(hex(&fill.color));
+                }
+                Action::FloodFill { color, .. } => {
+                    stats.flood_fills += 1;
+                    colors.insert(packed_hex(*color));
+                }
+                Action::Text(text) => {
+                    stats.texts += 1;
+                    stats.text_chars += text.text.chars().count();
+                    colors.insert(packed_hex(text.color));
+                }
+                _ => {}
+            }
+        }
+
+        widths.sort_by(f64::total_cmp);
+        widths.dedup();
+        stats.colors = colors.into_iter().collect();
+        stats.widths = widths;
+        stats
+    }
+}
+
+pub fn line_type_name(line_type: LineType) -> &'static str {
+    match line_type {
+        LineType::None => "none",
+        LineType::Pen => "pen",
+        LineType::Eraser => "eraser",
+        LineType::Brush => "brush",
+        LineType::Tone => "tone",
+        LineType::Dodge => "dodge",
+        LineType::Burn => "burn",
+        LineType::Blur => "blur",
+    }
+}
+
+fn hex(color: &Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+// Text and flood fill colors are stored as 0xBBGGRR
+fn packed_hex(color: u32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color & 0xff, (color >> 8) & 0xff, (color >> 16) & 0xff)
+}
@@ -0,0 +1,333 @@
+//! Checking replays without rendering them, for upload pipelines.
+//!
+//! Errors are problems that make the renderer fail or silently skip an action:
+//! missing or mistyped arguments, bad layer indices, out-of-range colors.
+//! Warnings flag input that still renders but is suspicious, like drawing outside
+//! the canvas or pasting before anything was copied. Action indices count actions
+//! after `PchFile::fix_actions`, the same numbering used for rendered frames.
+
+use crate::limits::{LimitExceeded, Limits};
+use crate::renderer::NEO_LAYERS;
+use crate::{ActionValue, PchFile, PchHeader};
+use serde::Serialize;
+use std::ops::Range;
+
+const MAX_ISSUES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub code: &'static str, // Stable identifier for pipelines, e.g. "layer-range"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<usize>, // None for problems with the file as a whole
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub valid: bool, // No errors; warnings are allowed
+    pub width: u16,
+    pub height: u16,
+    pub actions: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub issues: Vec<Issue>, // At most the first 1000, `errors` and `warnings` count all
+}
+
+impl ValidationReport {
+    fn new(width: u16, height: u16, actions: usize) -> Self {
+        Self { valid: true, width, height, actions, errors: 0, warnings: 0, issues: Vec::new() }
+    }
+
+    fn add(&mut self, severity: Severity, code: &'static str, action: Option<usize>, message: String) {
+        match severity {
+            Severity::Error => {
+                self.errors += 1;
+                self.valid = false;
+            }
+            Severity::Warning => self.warnings += 1,
+        }
+        if self.issues.len() < MAX_ISSUES {
+            self.issues.push(Issue { severity, code, action, message });
+        }
+    }
+}
+
+/// Validate the raw contents of a `.pch` file. The header is checked first; the
+/// actions are only checked when it is usable and the data decodes within `limits`.
+pub fn validate_bytes(data: &[u8], limits: &Limits) -> ValidationReport {
+    let header = match PchFile::read_header(data) {
+        Ok(header) => header,
+        Err(e) => {
+            let mut report = ValidationReport::new(0, 0, 0);
+            report.add(Severity::Error, "unreadable", None, e.to_string());
+            return report;
+        }
+    };
+
+    let mut report = ValidationReport::new(header.width, header.height, 0);
+    check_header(&header, &mut report);
+    if let Err(e) = limits.check_canvas(header.width as u32, header.height as u32) {
+        report.add(Severity::Error, "limit", None, e.to_string());
+    }
+    if !report.valid {
+        return report;
+    }
+
+    match PchFile::from_bytes_with_limits(data, limits) {
+        Ok(mut pch) => {
+            pch.fix_actions();
+            check_actions(&pch, report)
+        }
+        Err(e) => {
+            let code = if e.downcast_ref::<LimitExceeded>().is_some() { "limit" } else { "unreadable" };
+            report.add(Severity::Error, code, None, e.to_string());
+            report
+        }
+    }
+}
+
+/// Validate the header and every action of a decoded replay
+pub fn validate(pch: &PchFile) -> ValidationReport {
+    let mut pch = pch.clone();
+    pch.fix_actions();
+
+    let mut report = ValidationReport::new(pch.header.width, pch.header.height, 0);
+    check_header(&pch.header, &mut report);
+    check_actions(&pch, report)
+}
+
+fn check_header(header: &PchHeader, report: &mut ValidationReport) {
+    if &header.magic != b"NEO " {
+        report.add(Severity::Error, "magic", None, format!("Header magic is {:?}, not \"NEO \"", header.magic));
+    }
+    if header.width == 0 || header.height == 0 {
+        report.add(Severity::Error, "canvas-size", None, format!("Canvas is {}x{}", header.width, header.height));
+    }
+    if header.reserved != [0; 4] {
+        report.add(Severity::Warning, "reserved", None, format!("Reserved header bytes are set: {:?}", header.reserved));
+    }
+}
+
+// Actions are expected to be normalized with `fix_actions` already
+fn check_actions(pch: &PchFile, mut report: ValidationReport) -> ValidationReport {
+    report.actions = pch.actions.len();
+    let (width, height) = (pch.header.width as f64, pch.header.height as f64);
+    let mut checker = Checker { report, width, height, index: 0, copied: false };
+    for (index, values) in pch.actions.iter().enumerate() {
+        checker.index = index;
+        checker.check_action(values);
+    }
+    checker.report
+}
+
+struct Checker {
+    report: ValidationReport,
+    width: f64,
+    height: f64,
+    index: usize,
+    copied: bool, // A copy happened earlier, so paste has something to use
+}
+
+impl Checker {
+    fn error(&mut self, code: &'static str, message: String) {
+        self.report.add(Severity::Error, code, Some(self.index), message);
+    }
+
+    fn warning(&mut self, code: &'static str, message: String) {
+        self.report.add(Severity::Warning, code, Some(self.index), message);
+    }
+
+    // Each check returns None once it has reported a problem that stops further checks
+    fn check_action(&mut self, values: &[ActionValue]) -> Option<()> {
+        let command = match values.first() {
+            Some(ActionValue::String(command)) => command.as_str(),
+            Some(value) => {
+                self.error("command", format!("Command is {:?}, not a string", value));
+                return None;
+            }
+            None => {
+                self.error("command", "Action is empty".to_string());
+                return None;
+            }
+        };
+
+        match command {
+            "clearCanvas" | "restore" => {}
+            "eraseAll" => {
+                self.arguments(command, values, 2)?;
+                self.layer(values)?;
+            }
+            "freeHand" => self.stroke(command, values, 12)?,
+            "line" => self.stroke(command, values, 16)?,
+            "bezier" => self.stroke(command, values, 20)?,
+            "fill" => {
+                self.arguments(command, values, 16)?;
+                self.layer(values)?;
+                let numbers = self.numbers(values, 2..16)?;
+                self.colors(&numbers[..7])?;
+                self.pen_settings(numbers[7], numbers[8]);
+                let fill_type = numbers[13];
+                if !(20.0..=23.0).contains(&fill_type) || fill_type.fract() != 0.0 {
+                    self.warning("fill-type", format!("Unknown fill type {}, nothing is drawn", fill_type));
+                }
+                self.rect(command, &numbers[9..13]);
+            }
+            "floodFill" => {
+                self.arguments(command, values, 5)?;
+                self.layer(values)?;
+                let numbers = self.numbers(values, 2..5)?;
+                self.point(command, numbers[0], numbers[1]);
+                self.packed_color(numbers[2], 32);
+            }
+            "text" => {
+                self.arguments(command, values, 9)?;
+                self.layer(values)?;
+                let numbers = self.numbers(values, 2..6)?;
+                if !matches!(values[6], ActionValue::String(_)) {
+                    self.error("argument-type", format!("Argument 6 of text is {:?}, not a string", values[6]));
+                }
+                self.point(command, numbers[0], numbers[1]);
+                self.packed_color(numbers[2], 24);
+                if !(0.0..=1.0).contains(&numbers[3]) {
+                    self.warning("alpha-range", format!("Text alpha {} is outside 0 to 1", numbers[3]));
+                }
+            }
+            "copy" | "merge" => {
+                self.arguments(command, values, 6)?;
+                self.layer(values)?;
+                let numbers = self.numbers(values, 2..6)?;
+                self.rect(command, &numbers);
+                self.copied |= command == "copy";
+            }
+            "paste" => {
+                self.arguments(command, values, 8)?;
+                self.layer(values)?;
+                let numbers = self.numbers(values, 2..8)?;
+                if !self.copied {
+                    self.warning("paste-without-copy", "Paste before any copy, nothing is drawn".to_string());
+                }
+                let (dx, dy) = (numbers[4], numbers[5]);
+                self.rect(command, &[numbers[0] + dx, numbers[1] + dy, numbers[2], numbers[3]]);
+            }
+            _ => self.warning("unknown-command", format!("Unknown command {:?} is skipped", command)),
+        }
+        Some(())
+    }
+
+    fn stroke(&mut self, command: &str, values: &[ActionValue], min_len: usize) -> Option<()> {
+        self.arguments(command, values, min_len)?;
+        self.layer(values)?;
+        let settings = self.numbers(values, 2..12)?;
+        self.colors(&settings[..7])?;
+        self.pen_settings(settings[7], settings[8]);
+        let line_type = settings[9];
+        if !(0.0..=7.0).contains(&line_type) || line_type.fract() != 0.0 {
+            self.warning("line-type", format!("Unknown line type {}, drawn as none", line_type));
+        }
+
+        let points = self.numbers(values, 12..values.len())?;
+        if points.len() % 2 != 0 {
+            self.warning("odd-coordinates", format!("{} has an x without a y, which is ignored", command));
+        }
+        let margin = settings[7].max(0.0);
+        if let Some(point) = points.chunks_exact(2).find(|point| !self.inside(point[0], point[1], margin)) {
+            self.warning("off-canvas", format!("{} reaches ({}, {}), outside the canvas", command, point[0], point[1]));
+        }
+        Some(())
+    }
+
+    fn arguments(&mut self, command: &str, values: &[ActionValue], min_len: usize) -> Option<()> {
+        if values.len() < min_len {
+            self.error("arguments", format!("{} needs {} values, got {}", command, min_len, values.len()));
+            return None;
+        }
+        Some(())
+    }
+
+    fn layer(&mut self, values: &[ActionValue]) -> Option<usize> {
+        let layer = match values[1] {
+            ActionValue::Number(n) => n,
+            ActionValue::Integer(i) => i as f64,
+            ref value => {
+                self.error("argument-type", format!("Layer is {:?}, not a number", value));
+                return None;
+            }
+        };
+        if layer < 0.0 || layer >= NEO_LAYERS as f64 || layer.fract() != 0.0 {
+            self.error("layer-range", format!("Layer {} does not exist, the action is skipped", layer));
+            return None;
+        }
+        Some(layer as usize)
+    }
+
+    fn numbers(&mut self, values: &[ActionValue], range: Range<usize>) -> Option<Vec<f64>> {
+        let mut numbers = Vec::with_capacity(range.len());
+        for index in range {
+            match values[index] {
+                ActionValue::Number(n) if n.is_finite() => numbers.push(n),
+                ActionValue::Integer(i) => numbers.push(i as f64),
+                ref value => {
+                    self.error("argument-type", format!("Argument {} is {:?}, not a number", index, value));
+                    return None;
+                }
+            }
+        }
+        Some(numbers)
+    }
+
+    // Pen color as RGBA followed by the mask color as RGB
+    fn colors(&mut self, channels: &[f64]) -> Option<()> {
+        if let Some(channel) = channels.iter().find(|&&c| !(0.0..=255.0).contains(&c) || c.fract() != 0.0) {
+            self.error("color-range", format!("Color channel {} is not a whole number from 0 to 255", channel));
+            return None;
+        }
+        Some(())
+    }
+
+    // Colors of text (0xBBGGRR) and flood fills (0xAABBGGRR)
+    fn packed_color(&mut self, color: f64, bits: i32) {
+        if !(0.0..2f64.powi(bits)).contains(&color) || color.fract() != 0.0 {
+            self.error("color-range", format!("Color {} is not a {}-bit value", color, bits));
+        }
+    }
+
+    fn pen_settings(&mut self, width: f64, mask_type: f64) {
+        if width < 0.0 {
+            self.error("width-range", format!("Pen width {} is negative", width));
+        }
+        if !(0.0..=4.0).contains(&mask_type) || mask_type.fract() != 0.0 {
+            self.warning("mask-type", format!("Unknown mask type {}, no mask is used", mask_type));
+        }
+    }
+
+    fn inside(&self, x: f64, y: f64, margin: f64) -> bool {
+        (-margin..=self.width + margin).contains(&x) && (-margin..=self.height + margin).contains(&y)
+    }
+
+    fn point(&mut self, command: &str, x: f64, y: f64) {
+        if !self.inside(x, y, 0.0) {
+            self.warning("off-canvas", format!("{} at ({}, {}) is outside the canvas", command, x, y));
+        }
+    }
+
+    // Rectangle as x, y, width, height
+    fn rect(&mut self, command: &str, rect: &[f64]) {
+        let (x, y, width, height) = (rect[0], rect[1], rect[2], rect[3]);
+        if width < 0.0 || height < 0.0 {
+            self.warning("rect-size", format!("{} has a negative size {}x{}, nothing is drawn", command, width, height));
+        } else if x < 0.0 || y < 0.0 || x + width > self.width || y + height > self.height {
+            self.warning(
+                "off-canvas",
+                format!("{} rectangle {}x{} at ({}, {}) extends outside the canvas", command, width, height, x, y),
+            );
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Comparing the final state of a replay with the picture that was posted with it.
+//!
+//! Boards keep both files, and a replay that does not end in its posted picture
+//! was edited, swapped or recorded by a broken client. Small differences are
+//! normal: the posted PNG went through the browser's canvas, whose antialiasing
+//! and text rendering differ slightly from ours. `VerifyOptions` sets how much of
+//! that to accept.
+
+use crate::limits::Limits;
+use crate::renderer::Renderer;
+use crate::PchFile;
+use anyhow::{bail, Result};
+use image::{DynamicImage, Rgb, RgbImage};
+use serde::Serialize;
+
+const SSIM_WINDOW: u32 = 8;
+const SSIM_STEP: usize = 4;
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    pub tolerance: u8, // Largest per-channel difference still counted as equal
+    pub min_psnr: Option<f64>, // In dB; also fails when below, regardless of `tolerance`
+    pub min_ssim: Option<f64>, // 0.0-1.0
+    pub max_differing: f64, // Fraction of pixels allowed over `tolerance`, 0.0 for none
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    pub matches: bool, // Within every threshold of the options
+    pub exact: bool, // Pixel for pixel identical
+    pub width: u32,
+    pub height: u32,
+    pub max_difference: u8, // Largest per-channel difference of any pixel
+    pub differing_pixels: usize, // Pixels with a channel difference over the tolerance
+    pub differing_fraction: f64,
+    pub psnr: f64, // In dB, infinite (null in JSON) for identical images
+    pub ssim: f64, // Mean structural similarity of the luma, 1.0 for identical images
+}
+
+/// Composite image after every action, as posted at the end of a session
+pub fn render_final(pch: &PchFile, limits: &Limits) -> Result<RgbImage> {
+    let mut pch = pch.clone();
+    pch.fix_actions();
+    let (width, height) = (pch.header.width as u32, pch.header.height as u32);
+    limits.check_canvas(width, height)?;
+    let mut renderer = Renderer::new(width, height);
+    renderer.set_time_limit(limits.max_render_time);
+    renderer.render_to(&pch, pch.actions.len())?;
+    Ok(renderer.canvas.composite())
+}
+
+/// Posted image without transparency, drawn over white like the canvas
+pub fn flatten(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let over_white = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32) + 127) / 255) as u8;
+        Rgb([over_white(r), over_white(g), over_white(b)])
+    })
+}
+
+/// Render `pch` and compare the result with the posted image
+pub fn verify(pch: &PchFile, posted: &DynamicImage, options: &VerifyOptions, limits: &Limits) -> Result<Comparison> {
+    compare(&render_final(pch, limits)?, &flatten(posted), options)
+}
+
+/// Compare two images of the same size
+pub fn compare(rendered: &RgbImage, posted: &RgbImage, options: &VerifyOptions) -> Result<Comparison> {
+    check_size(rendered, posted)?;
+
+    let mut max_difference = 0;
+    let mut differing_pixels = 0;
+    let mut squared_error = 0.0;
+    for (a, b) in rendered.pixels().zip(posted.pixels()) {
+        let difference = pixel_difference(a, b);
+        max_difference = max_difference.max(difference);
+        if difference > options.tolerance {
+            differing_pixels += 1;
+        }
+        squared_error += a.0.iter().zip(b.0).map(|(&x, y)| (x as f64 - y as f64).powi(2)).sum::<f64>();
+    }
+
+    let pixels = (rendered.width() as usize * rendered.height() as usize).max(1);
+    let mse = squared_error / (pixels * 3) as f64;
+    let psnr = if mse == 0.0 { f64::INFINITY } else { 10.0 * (255.0 * 255.0 / mse).log10() };
+    let ssim = ssim(rendered, posted);
+    let differing_fraction = differing_pixels as f64 / pixels as f64;
+
+    let matches = differing_fraction <= options.max_differing
+        && options.min_psnr.is_none_or(|min| psnr >= min)
+        && options.min_ssim.is_none_or(|min| ssim >= min);
+    Ok(Comparison {
+        matches,
+        exact: max_difference == 0,
+        width: rendered.width(),
+        height: rendered.height(),
+        max_difference,
+        differing_pixels,
+        differing_fraction,
+        psnr,
+        ssim,
+    })
+}
+
+/// Image of where the two differ: pixels within `tolerance` show the rendered
+/// image faded, the others are red, darker for larger differences
+pub fn diff_heatmap(rendered: &RgbImage, posted: &RgbImage, tolerance: u8) -> Result<RgbImage> {
+    check_size(rendered, posted)?;
+    Ok(RgbImage::from_fn(rendered.width(), rendered.height(), |x, y| {
+        let (a, b) = (rendered.get_pixel(x, y), posted.get_pixel(x, y));
+        let difference = pixel_difference(a, b);
+        if difference > tolerance {
+            let shade = 255 - difference;
+            Rgb([255 - difference / 2, shade / 2, shade / 2])
+        } else {
+            let faded = 255 - (255 - luma(a) as u8) / 4;
+            Rgb([faded, faded, faded])
+        }
+    }))
+}
+
+fn check_size(rendered: &RgbImage, posted: &RgbImage) -> Result<()> {
+    if rendered.dimensions() != posted.dimensions() {
+        bail!(
+            "Image is {}x{}, but the replay is {}x{}",
+            posted.width(),
+            posted.height(),
+            rendered.width(),
+            rendered.height()
+        );
+    }
+    Ok(())
+}
+
+fn pixel_difference(a: &Rgb<u8>, b: &Rgb<u8>) -> u8 {
+    a.0.iter().zip(b.0).map(|(&x, y)| x.abs_diff(y)).max().unwrap_or(0)
+}
+
+fn luma(pixel: &Rgb<u8>) -> f64 {
+    let [r, g, b] = pixel.0;
+    0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64
+}
+
+// Mean SSIM of the luma over 8x8 windows every 4 pixels, or one window for
+// images smaller than that
+fn ssim(a: &RgbImage, b: &RgbImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let window_width = SSIM_WINDOW.min(a.width());
+    let window_height = SSIM_WINDOW.min(a.height());
+    if window_width == 0 || window_height == 0 {
+        return 1.0;
+    }
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for top in (0..=a.height() - window_height).step_by(SSIM_STEP) {
+        for left in (0..=a.width() - window_width).step_by(SSIM_STEP) {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in top..top + window_height {
+                for x in left..left + window_width {
+                    let (la, lb) = (luma(a.get_pixel(x, y)), luma(b.get_pixel(x, y)));
+                    sum_a += la;
+                    sum_b += lb;
+                    sum_aa += la * la;
+                    sum_bb += lb * lb;
+                    sum_ab += la * lb;
+                }
+            }
+            let n = (window_width * window_height) as f64;
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let variance_a = sum_aa / n - mean_a * mean_a;
+            let variance_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
@@ -0,0 +1,36 @@
+//! Contact sheet sizes that cannot be allocated.
+
+use neo_replay_rs::contact_sheet::{contact_sheet, Sampling, SheetOptions};
+use neo_replay_rs::{ActionValue, PchFile, PchHeader};
+
+fn restores(width: u16, height: u16, count: usize) -> PchFile {
+    PchFile {
+        header: PchHeader { magic: *b"NEO ", width, height, reserved: [0; 4] },
+        actions: vec![vec![ActionValue::String("restore".to_string())]; count],
+    }
+}
+
+#[test]
+fn small_sheet_is_rendered() {
+    let options = SheetOptions { sampling: Sampling::Even(4), columns: Some(4), ..Default::default() };
+    let (image, atlas) = contact_sheet(&restores(40, 30, 4), &options).unwrap();
+    assert_eq!(image.dimensions(), (4 + 4 * 44, 4 + 44));
+    assert_eq!((atlas.columns, atlas.rows), (4, 1));
+}
+
+#[test]
+fn overflowing_size_is_an_error() {
+    let options = SheetOptions { sampling: Sampling::Even(2), columns: Some(2), padding: u32::MAX / 2, ..Default::default() };
+    let error = contact_sheet(&restores(40, 30, 2), &options).unwrap_err();
+    assert!(error.to_string().contains("too large"), "{}", error);
+
+    let options = SheetOptions { padding: u32::MAX, ..Default::default() };
+    assert!(contact_sheet(&restores(40, 30, 2), &options).is_err());
+}
+
+#[test]
+fn oversized_sheet_is_an_error() {
+    let options = SheetOptions { sampling: Sampling::Even(20), tile_width: 4096, ..Default::default() };
+    let error = contact_sheet(&restores(4096, 4096, 20), &options).unwrap_err();
+    assert!(error.to_string().contains("exceeds"), "{}", error);
+}
@@ -0,0 +1,69 @@
+//! Lossless round trips through `PchFile::to_bytes` and the text dumps.
+
+use neo_replay_rs::{dump, PchFile};
+
+// Every command NEO records, as a file decodes them, plus an action only the raw
+// fallback of the dumps can hold
+const ALL_COMMANDS: &str = r#"[
+    ["clearCanvas"],
+    ["eraseAll", 1],
+    ["freeHand", 0, 10, 20, 30, 255, 0, 0, 0, 3, 0, 1, 5, 5, 6, 7, 9.5, 12],
+    ["line", 1, 0, 0, 255, 128, 255, 255, 255, 1, 2, 3, 0, 0, 39, 29],
+    ["bezier", 0, 255, 0, 0, 255, 0, 0, 0, 2, 0, 4, 1, 1, 10, 0, 20, 29, 38, 28],
+    ["fill", 0, 0, 255, 0, 255, 0, 0, 0, 1, 0, 5, 5, 10, 10, 21],
+    ["floodFill", 1, 3, 4, 4278190335],
+    ["text", 0, 10, 20, 16711680, 0.5, "hello", "16px", ""],
+    ["text", 1, 2, 3, 0, 1, "serif", "12px", "serif"],
+    ["copy", 0, 1, 2, 10, 10],
+    ["paste", 0, 1, 2, 10, 10, 5, -3],
+    ["merge", 0, 0, 0, 40, 30],
+    ["restore"],
+    ["stamp", 1, "star"]
+]"#;
+
+fn all_commands() -> PchFile {
+    let json: Vec<u16> = ALL_COMMANDS.encode_utf16().collect();
+    let mut data = b"NEO ".to_vec();
+    data.extend_from_slice(&40u16.to_le_bytes());
+    data.extend_from_slice(&30u16.to_le_bytes());
+    data.extend_from_slice(&[0, 0, 7, 0]);
+    data.extend_from_slice(&lz_str::compress_to_uint8_array(&json[..]));
+    PchFile::from_bytes(&data).unwrap()
+}
+
+fn assert_same(decoded: &PchFile, original: &PchFile) {
+    assert_eq!(decoded.header.magic, original.header.magic);
+    assert_eq!((decoded.header.width, decoded.header.height), (original.header.width, original.header.height));
+    assert_eq!(decoded.header.reserved, original.header.reserved);
+    assert_eq!(decoded.actions, original.actions);
+}
+
+#[test]
+fn bytes_round_trip() {
+    let pch = all_commands();
+    assert_eq!(pch.actions.len(), 14);
+    assert_same(&PchFile::from_bytes(&pch.to_bytes().unwrap()).unwrap(), &pch);
+}
+
+#[test]
+fn ndjson_round_trip() {
+    let pch = all_commands();
+    let text = dump::to_ndjson(&pch).unwrap();
+    // Only the unknown command needs the raw fallback
+    let raw: Vec<&str> = text.lines().filter(|line| line.starts_with(r#"{"raw""#)).collect();
+    assert_eq!(raw, [r#"{"raw":["stamp",1,"star"]}"#]);
+    assert_same(&dump::from_ndjson(&text).unwrap(), &pch);
+}
+
+#[test]
+fn json_round_trip() {
+    let pch = all_commands();
+    assert_same(&dump::from_json(&dump::to_json(&pch).unwrap()).unwrap(), &pch);
+}
+
+#[test]
+fn dump_then_bytes_round_trip() {
+    let pch = all_commands();
+    let imported = dump::from_ndjson(&dump::to_ndjson(&pch).unwrap()).unwrap();
+    assert_same(&PchFile::from_bytes(&imported.to_bytes().unwrap()).unwrap(), &pch);
+}
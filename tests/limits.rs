@@ -0,0 +1,35 @@
+//! Render time limits on replays built to be slow.
+
+use neo_replay_rs::limits::LimitExceeded;
+use neo_replay_rs::renderer::Renderer;
+use neo_replay_rs::{ActionValue, PchFile, PchHeader};
+use std::time::{Duration, Instant};
+
+// One freeHand of `points` points zigzagging across the canvas, `width` pixels wide
+fn zigzag_stroke(size: u16, points: usize, width: f64) -> PchFile {
+    let mut action = vec![ActionValue::String("freeHand".to_string())];
+    let state = [0.0, 0.0, 0.0, 0.0, 255.0, 0.0, 0.0, 0.0, width, 0.0, 1.0];
+    action.extend(state.iter().map(|&n| ActionValue::Number(n)));
+    for i in 0..points {
+        let x = if i % 2 == 0 { 0.0 } else { size as f64 - 1.0 };
+        action.push(ActionValue::Number(x));
+        action.push(ActionValue::Number(i as f64));
+    }
+    PchFile {
+        header: PchHeader { magic: *b"NEO ", width: size, height: size, reserved: [0; 4] },
+        actions: vec![action],
+    }
+}
+
+#[test]
+fn time_limit_stops_a_single_heavy_stroke() {
+    let pch = zigzag_stroke(1000, 400, 30.0);
+    let mut renderer = Renderer::new(1000, 1000);
+    renderer.set_time_limit(Some(Duration::from_millis(200)));
+
+    let started = Instant::now();
+    let error = renderer.render_to(&pch, pch.actions.len()).unwrap_err();
+    assert!(matches!(error.downcast_ref::<LimitExceeded>(), Some(LimitExceeded::RenderTime(_))));
+    // Without the check inside the stroke this takes tens of seconds
+    assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+}
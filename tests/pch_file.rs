@@ -0,0 +1,36 @@
+//! Decoding and encoding whole `.pch` files.
+
+use neo_replay_rs::{ActionValue, PchFile};
+
+// A file as NEO writes it: the header, then the UTF-16 code units of the JSON
+fn neo_file(width: u16, height: u16, json: &[u16]) -> Vec<u8> {
+    let mut data = b"NEO ".to_vec();
+    data.extend_from_slice(&width.to_le_bytes());
+    data.extend_from_slice(&height.to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&lz_str::compress_to_uint8_array(json));
+    data
+}
+
+fn text_value(pch: &PchFile) -> &str {
+    match &pch.actions[0][1] {
+        ActionValue::String(text) => text,
+        value => panic!("Expected a string, got {:?}", value),
+    }
+}
+
+#[test]
+fn characters_outside_the_bmp_are_dropped_like_before() {
+    let json: Vec<u16> = r#"[["text","Hi 😀!"]]"#.encode_utf16().collect();
+    let pch = PchFile::from_bytes(&neo_file(40, 30, &json)).unwrap();
+    assert_eq!(text_value(&pch), "Hi !");
+}
+
+#[test]
+fn lone_surrogates_are_dropped() {
+    let mut json: Vec<u16> = r#"[["text","a"#.encode_utf16().collect();
+    json.push(0xd800);
+    json.extend(r#"b"]]"#.encode_utf16());
+    let pch = PchFile::from_bytes(&neo_file(40, 30, &json)).unwrap();
+    assert_eq!(text_value(&pch), "ab");
+}
@@ -0,0 +1,127 @@
+//! Salvaging actions from truncated and corrupted files.
+
+use neo_replay_rs::limits::Limits;
+use neo_replay_rs::recover::{recover, RecoveryReport};
+use neo_replay_rs::{lzstring, ActionValue, PchFile, PchHeader};
+
+const ACTIONS: [&str; 5] = [
+    r#"["fill",0,255,0,0,255,0,0,0,1,0,5,5,10,10,21]"#,
+    r#"["text",0,1,2,0,1,"one","12px",""]"#,
+    r#"["text",0,1,2,0,1,"two","12px",""]"#,
+    r#"["freeHand",0,0,0,0,255,0,0,0,3,0,1,5,5,6,7,8,9,10,11]"#,
+    r#"["restore"]"#,
+];
+
+fn sample(actions: &[&str]) -> PchFile {
+    PchFile {
+        header: PchHeader { magic: *b"NEO ", width: 40, height: 30, reserved: [0; 4] },
+        actions: actions.iter().map(|json| serde_json::from_str(json).unwrap()).collect(),
+    }
+}
+
+// Characters of the JSON list up to and including each action
+fn action_ends() -> Vec<usize> {
+    ACTIONS
+        .iter()
+        .scan(0, |end, json| {
+            *end += json.len() + 1; // The opening bracket or the comma before it
+            Some(*end)
+        })
+        .collect()
+}
+
+fn salvage(data: &[u8]) -> (Vec<Vec<ActionValue>>, RecoveryReport) {
+    let (pch, report) = recover(data, &Limits::unlimited()).unwrap();
+    assert_eq!(report.actions, pch.actions.len());
+    (pch.actions, report)
+}
+
+#[test]
+fn complete_file_is_untouched() {
+    let pch = sample(&ACTIONS);
+    let (actions, report) = salvage(&pch.to_bytes().unwrap());
+    assert_eq!(actions, pch.actions);
+    assert!(report.complete && report.stream_complete && report.list_complete);
+    assert_eq!(report.unused_chars, 0);
+    assert_eq!(report.problem, None);
+}
+
+#[test]
+fn empty_list() {
+    let (actions, report) = salvage(&sample(&[]).to_bytes().unwrap());
+    assert!(actions.is_empty());
+    assert!(report.complete && report.stream_complete && report.list_complete);
+    assert_eq!((report.decompressed_chars, report.unused_chars), (2, 0));
+}
+
+#[test]
+fn truncated_at_chosen_offsets() {
+    let pch = sample(&ACTIONS);
+    let data = pch.to_bytes().unwrap();
+    assert_eq!((data.len() - 12) % 2, 0);
+
+    // (file length, salvaged actions, unused characters)
+    let cases = [
+        (data.len() - 1, 5, 0), // Odd byte count, only the closing bracket is lost
+        (data.len() - 2, 5, 0),
+        (68, 2, 4), // Stops in the middle of `["text"`
+        (43, 1, 0), // Odd byte count right after the first action
+        (13, 0, 0), // Half a character
+    ];
+    for (len, count, unused) in cases {
+        let (actions, report) = salvage(&data[..len]);
+        assert_eq!(actions, pch.actions[..count], "cut at {}", len);
+        assert!(!report.complete && !report.stream_complete && !report.list_complete, "cut at {}", len);
+        assert_eq!(report.unused_chars, unused, "cut at {}", len);
+        assert!(report.problem.is_some());
+    }
+}
+
+#[test]
+fn truncated_at_every_offset() {
+    let pch = sample(&ACTIONS);
+    let data = pch.to_bytes().unwrap();
+    let (full, complete) = lzstring::decompress_partial(&data[12..], usize::MAX).unwrap();
+    assert!(complete);
+    let ends = action_ends();
+
+    for len in 12..data.len() {
+        let (partial, complete) = lzstring::decompress_partial(&data[12..len], usize::MAX).unwrap();
+        assert!(full.starts_with(&partial), "cut at {}", len);
+        // An empty payload is a complete lz-string stream holding nothing
+        assert_eq!(complete, len == 12, "cut at {}", len);
+        if len % 2 == 1 {
+            assert_eq!(partial, lzstring::decompress_partial(&data[12..len - 1], usize::MAX).unwrap().0);
+        }
+
+        let (actions, report) = salvage(&data[..len]);
+        let count = ends.iter().take_while(|&&end| end <= partial.len()).count();
+        let used = if count == 0 { 0 } else { ends[count - 1] };
+        assert_eq!(actions, pch.actions[..count], "cut at {}", len);
+        assert_eq!(report.decompressed_chars, partial.len(), "cut at {}", len);
+        assert_eq!(report.unused_chars, partial.len() - used, "cut at {}", len);
+        assert!(!report.list_complete && !report.complete, "cut at {}", len);
+    }
+}
+
+#[test]
+fn corrupted_middle_byte() {
+    let pch = sample(&ACTIONS);
+    let mut data = pch.to_bytes().unwrap();
+    data[75] ^= 0x55;
+
+    // The damaged code still decodes, so the stream ends properly but the JSON
+    // breaks inside the third action
+    let (decompressed, complete) = lzstring::decompress_partial(&data[12..], usize::MAX).unwrap();
+    assert!(complete);
+    let (full, _) = lzstring::decompress_partial(&pch.to_bytes().unwrap()[12..], usize::MAX).unwrap();
+    assert_eq!(decompressed[..action_ends()[1]], full[..action_ends()[1]]);
+    assert_ne!(decompressed, full);
+
+    let (actions, report) = salvage(&data);
+    assert_eq!(actions, pch.actions[..2]);
+    assert!(report.stream_complete);
+    assert!(!report.list_complete && !report.complete);
+    assert_eq!(report.unused_chars, decompressed.len() - action_ends()[1]);
+    assert!(report.problem.unwrap().starts_with("Action 2 is invalid"));
+}
@@ -0,0 +1,200 @@
+//! Requests against a `neo-replay-server` listening on a free localhost port.
+#![cfg(feature = "server")]
+
+use neo_replay_rs::{ActionValue, PchFile, PchHeader};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start(args: &[&str]) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_neo-replay-server"))
+            .args(["--addr", "127.0.0.1:0", "--workers", "2"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to start server");
+
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        let addr = line.trim().strip_prefix("Listening on http://").expect("Missing listen address").to_string();
+        // Keep draining the request log so the server never blocks on a full pipe
+        thread::spawn(move || std::io::copy(&mut stdout, &mut std::io::sink()));
+        Server { child, addr }
+    }
+
+    /// Send one request and return the status code and body
+    fn request(&self, method: &str, path: &str, body: &[u8]) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            path,
+            self.addr,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").expect("Incomplete response");
+        let head = String::from_utf8_lossy(&response[..split]);
+        let status = head.split(' ').nth(1).and_then(|code| code.parse().ok()).expect("Missing status");
+        (status, response[split + 4..].to_vec())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// A 40x30 canvas with one filled rectangle
+fn sample_pch() -> Vec<u8> {
+    replay_of_fills(1)
+}
+
+// A 40x30 canvas with `count` red 10x10 rectangles side by side
+fn replay_of_fills(count: usize) -> Vec<u8> {
+    let actions = (0..count)
+        .map(|i| {
+            let x = 5.0 + 10.0 * i as f64;
+            let fill = [0.0, 255.0, 0.0, 0.0, 255.0, 0.0, 0.0, 0.0, 1.0, 0.0, x, 5.0, 10.0, 10.0, 21.0];
+            let mut action = vec![ActionValue::String("fill".to_string())];
+            action.extend(fill.iter().map(|&n| ActionValue::Number(n)));
+            action
+        })
+        .collect();
+    let pch = PchFile {
+        header: PchHeader { magic: *b"NEO ", width: 40, height: 30, reserved: [0; 4] },
+        actions,
+    };
+    pch.to_bytes().unwrap()
+}
+
+// Top-level chunks of a RIFF WebP file as (fourcc, payload)
+fn riff_chunks(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    assert_eq!(&data[..4], b"RIFF");
+    assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize, data.len() - 8);
+    assert_eq!(&data[8..12], b"WEBP");
+
+    let mut chunks = Vec::new();
+    let mut rest = &data[12..];
+    while !rest.is_empty() {
+        let size = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        chunks.push((&rest[..4], &rest[8..8 + size]));
+        rest = &rest[(8 + size + size % 2).min(rest.len())..];
+    }
+    chunks
+}
+
+fn u24(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+#[test]
+fn health() {
+    let server = Server::start(&[]);
+    assert_eq!(server.request("GET", "/health", b""), (200, b"ok\n".to_vec()));
+}
+
+#[test]
+fn render_returns_png_of_canvas_size() {
+    let server = Server::start(&[]);
+    let (status, body) = server.request("POST", "/render", &sample_pch());
+    assert_eq!(status, 200);
+    let image = image::load_from_memory(&body).unwrap().to_rgb8();
+    assert_eq!(image.dimensions(), (40, 30));
+    assert_eq!(image.get_pixel(10, 10).0, [255, 0, 0]);
+    assert_eq!(image.get_pixel(30, 20).0, [255, 255, 255]);
+}
+
+#[test]
+fn thumbnail_is_scaled_down_png() {
+    let server = Server::start(&[]);
+    let (status, body) = server.request("POST", "/thumbnail?width=20", &sample_pch());
+    assert_eq!(status, 200);
+    let image = image::load_from_memory_with_format(&body, image::ImageFormat::Png).unwrap().to_rgb8();
+    assert_eq!(image.dimensions(), (20, 15));
+    assert_eq!(image.get_pixel(5, 5).0, [255, 0, 0]);
+}
+
+#[test]
+fn animation_is_webp_with_one_frame_per_sample() {
+    let server = Server::start(&[]);
+    let (status, body) = server.request("POST", "/animation?frames=3", &replay_of_fills(3));
+    assert_eq!(status, 200);
+
+    let chunks = riff_chunks(&body);
+    let (fourcc, vp8x) = chunks[0];
+    assert_eq!(fourcc, b"VP8X");
+    assert_eq!((u24(&vp8x[4..7]) + 1, u24(&vp8x[7..10]) + 1), (40, 30));
+    assert_eq!(chunks[1].0, b"ANIM");
+    let frames: Vec<&[u8]> = chunks.iter().filter(|(fourcc, _)| *fourcc == b"ANMF").map(|(_, data)| *data).collect();
+    assert_eq!(frames.len(), 3);
+    for frame in &frames {
+        assert_eq!((u24(&frame[6..9]) + 1, u24(&frame[9..12]) + 1), (40, 30));
+    }
+
+    // The container also has to be readable by a real decoder
+    let mut decoder = image_webp::WebPDecoder::new(std::io::Cursor::new(&body)).unwrap();
+    assert!(decoder.is_animated());
+    assert_eq!(decoder.dimensions(), (40, 30));
+    assert_eq!(decoder.num_frames(), 3);
+    let mut pixels = vec![0; decoder.output_buffer_size().unwrap()];
+    let channels = if decoder.has_alpha() { 4 } else { 3 };
+    for filled in 1..=3 {
+        decoder.read_frame(&mut pixels).unwrap();
+        // Each frame shows one more rectangle than the one before
+        for i in 0..3 {
+            let offset = (10 * 40 + 10 + 10 * i) * channels;
+            let expected = if i < filled { [255, 0, 0] } else { [255, 255, 255] };
+            assert_eq!(pixels[offset..offset + 3], expected, "frame {} rectangle {}", filled, i);
+        }
+    }
+}
+
+#[test]
+fn info_reports_replay() {
+    let server = Server::start(&[]);
+    let (status, body) = server.request("POST", "/info", &sample_pch());
+    assert_eq!(status, 200);
+    let info: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(info["width"], 40);
+    assert_eq!(info["height"], 30);
+    assert_eq!(info["actions"], 1);
+    assert_eq!(info["layers"], 2);
+}
+
+#[test]
+fn oversized_body_is_rejected() {
+    let server = Server::start(&["--max-body", "1024"]);
+    let (status, _) = server.request("POST", "/render", &[0; 4096]);
+    assert_eq!(status, 413);
+}
+
+#[test]
+fn invalid_file_is_bad_request() {
+    let server = Server::start(&[]);
+    let (status, _) = server.request("POST", "/render", b"not a replay");
+    assert_eq!(status, 400);
+}
+
+#[test]
+fn unknown_path_and_wrong_method() {
+    let server = Server::start(&[]);
+    assert_eq!(server.request("GET", "/missing", b"").0, 404);
+    assert_eq!(server.request("GET", "/render", b"").0, 405);
+    assert_eq!(server.request("POST", "/health", b"").0, 405);
+}